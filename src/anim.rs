@@ -0,0 +1,355 @@
+use crate::error::{Error, Result};
+use crate::input::{Raw, Source};
+use crate::options::RenderOptions;
+use crate::render::{self, RenderedFrame};
+use crossterm::{QueueableCommand, cursor};
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, DynamicImage, ImageError, ImageFormat, ImageReader};
+use std::io::{self, Cursor, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Frames meant to be shown for less than this are shown for
+/// `DEFAULT_DELAY` instead, as browsers do, which GIFs made for them count
+/// on.
+const MIN_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_DELAY: Duration = Duration::from_millis(100);
+
+/// How often a wait checks for Ctrl-C.
+const TICK: Duration = Duration::from_millis(50);
+
+/// An input opened for playing: an animation, or a reader to decode a still
+/// image from.
+pub enum Input {
+    Animation(Animation),
+    Image(ImageReader<Source>),
+}
+
+/// The frames of an animated GIF, PNG or WebP, how long each stays on
+/// screen and how many times the file asks for them to be played, 0 for
+/// forever.
+pub struct Animation {
+    frames: Vec<(DynamicImage, Duration)>,
+    plays: u32,
+}
+
+/// Decodes every frame of animated GIFs, PNGs and WebPs, the decoders taking
+/// care of how each frame is blended over and cleared from the previous
+/// ones. Everything else, including GIFs of a single frame, is handed back
+/// as a reader rewound to the start.
+pub fn open(reader: ImageReader<Source>, input: &Path) -> Result<Input> {
+    let format = match reader.format() {
+        Some(format @ (ImageFormat::Gif | ImageFormat::Png | ImageFormat::WebP)) => format,
+        _ => return Ok(Input::Image(reader)),
+    };
+    let mut data = Vec::new();
+    reader
+        .into_inner()
+        .read_to_end(&mut data)
+        .map_err(|e| Error::open(input, e))?;
+    let decoded = match format {
+        ImageFormat::Gif => GifDecoder::new(Cursor::new(&data))
+            .map(|decoder| Some((decoder.into_frames(), gif_plays(&data)))),
+        ImageFormat::Png => {
+            PngDecoder::new(Cursor::new(&data)).and_then(|decoder| match decoder.is_apng()? {
+                true => Ok(Some((decoder.apng()?.into_frames(), apng_plays(&data)))),
+                false => Ok(None),
+            })
+        }
+        _ => WebPDecoder::new(Cursor::new(&data)).map(|decoder| {
+            decoder
+                .has_animation()
+                .then(|| (decoder.into_frames(), webp_plays(&data)))
+        }),
+    };
+    let frames = decoded
+        .and_then(|decoded| {
+            let Some((frames, plays)) = decoded else {
+                return Ok(None);
+            };
+            let frames = frames
+                .map(|frame| {
+                    let frame = frame?;
+                    let delay = match Duration::from(frame.delay()) {
+                        delay if delay < MIN_DELAY => DEFAULT_DELAY,
+                        delay => delay,
+                    };
+                    Ok((DynamicImage::ImageRgba8(frame.into_buffer()), delay))
+                })
+                .collect::<std::result::Result<Vec<_>, ImageError>>()?;
+            Ok(Some(Animation { frames, plays }))
+        })
+        .map_err(|e| Error::image(input, e))?;
+    match frames {
+        Some(animation) if animation.frames.len() > 1 => {
+            let frames = animation.frames.len();
+            match animation.plays {
+                0 => log::info!("decoded {frames} frames, to be played forever"),
+                plays => log::info!("decoded {frames} frames, to be played {plays} times"),
+            }
+            Ok(Input::Animation(animation))
+        }
+        _ => Ok(Input::Image(ImageReader::with_format(
+            Source::Memory(Cursor::new(data)),
+            format,
+        ))),
+    }
+}
+
+/// How many times a GIF is played: once more than the loop count of its
+/// `NETSCAPE2.0` extension, forever when that is 0, or once without it.
+fn gif_plays(data: &[u8]) -> u32 {
+    const EXTENSION: &[u8] = b"NETSCAPE2.0";
+    // Extensions ahead of the first image are walked block by block, after
+    // the header, the screen descriptor and its global color table.
+    let mut at = 13;
+    if let Some(flags) = data.get(10).filter(|flags| *flags & 0x80 != 0) {
+        at += 3 << ((flags & 7) + 1);
+    }
+    while let Some(&[0x21, label]) = data.get(at..at + 2) {
+        at += 2;
+        let start = at;
+        // The data of an extension is a chain of blocks preceded by their
+        // length, ending with an empty one.
+        while let Some(&len) = data.get(at) {
+            at += 1 + len as usize;
+            if len == 0 {
+                break;
+            }
+        }
+        if label != 0xFF {
+            continue;
+        }
+        if let Some([11, rest @ ..]) = data.get(start..start + 16)
+            && let (app, [3, 1, lo, hi]) = rest.split_at(EXTENSION.len())
+            && app == EXTENSION
+        {
+            return match u16::from_le_bytes([*lo, *hi]) {
+                0 => 0,
+                count => u32::from(count) + 1,
+            };
+        }
+    }
+    1
+}
+
+/// How many times an APNG is played, from the `acTL` chunk ahead of its
+/// image data.
+fn apng_plays(data: &[u8]) -> u32 {
+    let word = |at: usize| {
+        data.get(at..at + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    // Chunks are a length, a type, the data and a checksum, after the
+    // 8 byte signature.
+    let mut at = 8;
+    while let (Some(len), Some(kind)) = (word(at), data.get(at + 4..at + 8)) {
+        match kind {
+            b"acTL" => return word(at + 12).unwrap_or(1),
+            b"IDAT" | b"fdAT" => break,
+            _ => at += 12 + len as usize,
+        }
+    }
+    1
+}
+
+/// How many times an animated WebP is played, from its `ANIM` chunk.
+fn webp_plays(data: &[u8]) -> u32 {
+    // Chunks are a tag, a little-endian length and the data, padded to an
+    // even length, after the 12 byte RIFF header.
+    let mut at = 12;
+    while let (Some(kind), Some(len)) = (data.get(at..at + 4), data.get(at + 4..at + 8)) {
+        let len = u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        match kind {
+            b"ANIM" => {
+                return data.get(at + 12..at + 14).map_or(1, |count| {
+                    u32::from(u16::from_le_bytes([count[0], count[1]]))
+                });
+            }
+            b"ANMF" => break,
+            _ => at += 8 + len + len % 2,
+        }
+    }
+    1
+}
+
+impl Animation {
+    /// Plays the frames in place `loops` times, or as many times as the file
+    /// says, 0 standing for forever, until Ctrl-C. Each frame is rendered
+    /// once and redrawn from then on.
+    pub fn play(&self, opts: &RenderOptions, loops: Option<u32>) -> Result<()> {
+        let loops = loops.unwrap_or(self.plays);
+        let mut playback = Playback::start(opts)?;
+        let mut rendered = Vec::with_capacity(self.frames.len());
+        for _ in (0..).take_while(|pass| loops == 0 || *pass < loops) {
+            for (i, (img, delay)) in self.frames.iter().enumerate() {
+                if i == rendered.len() {
+                    rendered.push(render::render(img, opts));
+                }
+                playback.draw(&rendered[i])?;
+                wait(*delay)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Plays an animated GIF, PNG or WebP in place, redrawing each frame over the
+/// previous one. Still images are rendered once, like `view` does.
+pub fn play(input: &Path, opts: &RenderOptions, loops: Option<u32>) -> Result<()> {
+    let reader = match open(crate::input::open(input)?, input)? {
+        Input::Animation(animation) => return animation.play(opts, loops),
+        Input::Image(reader) => reader,
+    };
+    let (img, bilevel) = crate::decode(reader, input, 1, None, opts)?;
+    let opts = RenderOptions {
+        bilevel,
+        ..opts.clone()
+    };
+    let out = render::render(&img, &opts);
+    io::stdout().lock().write_all(out.text.as_bytes())?;
+    Ok(())
+}
+
+/// Plays a stream of raw frames in place, each as soon as it has been read,
+/// so the producer sets the pace.
+pub fn play_raw(input: &Path, raw: Raw, opts: &RenderOptions) -> Result<()> {
+    let mut reader = raw.open(input)?;
+    let mut playback = Playback::start(opts)?;
+    while let Some(img) = raw.read_frame(&mut reader, input)? {
+        playback.draw(&render::render(&img, opts))?;
+        wait(Duration::ZERO)?;
+    }
+    Ok(())
+}
+
+/// Sleeps for `delay`, failing with [`Error::Interrupted`] as soon as Ctrl-C
+/// is pressed.
+fn wait(delay: Duration) -> Result<()> {
+    let end = Instant::now() + delay;
+    loop {
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            return Err(Error::Interrupted);
+        }
+        let left = end.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Ok(());
+        }
+        thread::sleep(left.min(TICK));
+    }
+}
+
+/// Set by Ctrl-C while frames are being played.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Redraws frames over each other, hiding the cursor and catching Ctrl-C
+/// while they are played, and gives both back when dropped, however
+/// playback ends.
+struct Playback<'a> {
+    opts: &'a RenderOptions,
+    /// Terminal rows the previous frame took up.
+    drawn_rows: u16,
+    /// Kitty image the previous frame was drawn as.
+    image: Option<u32>,
+}
+
+impl<'a> Playback<'a> {
+    fn start(opts: &'a RenderOptions) -> Result<Playback<'a>> {
+        io::stdout().lock().queue(cursor::Hide)?.flush()?;
+        interrupt::catch();
+        Ok(Playback {
+            opts,
+            drawn_rows: 0,
+            image: None,
+        })
+    }
+
+    /// Draws the frame over the previous one, whose kitty image is deleted
+    /// unless the frame reuses it.
+    fn draw(&mut self, out: &RenderedFrame) -> Result<()> {
+        let mut stdout = io::stdout().lock();
+        if let Some(id) = self.image.filter(|&id| out.image != Some(id)) {
+            stdout.write_all(render::delete_image(id, self.opts).as_bytes())?;
+        }
+        if self.drawn_rows > 0 {
+            stdout.queue(cursor::MoveToPreviousLine(self.drawn_rows))?;
+        }
+        stdout.write_all(out.text.as_bytes())?;
+        stdout.flush()?;
+        self.drawn_rows = out.rows as u16;
+        self.image = out.image;
+        Ok(())
+    }
+}
+
+impl Drop for Playback<'_> {
+    fn drop(&mut self) {
+        interrupt::release();
+        let mut stdout = io::stdout().lock();
+        // A finished animation leaves its last frame up like a still image,
+        // one cut short by Ctrl-C is taken off the screen.
+        if INTERRUPTED.load(Ordering::Relaxed)
+            && let Some(id) = self.image
+        {
+            let _ = stdout.write_all(render::delete_image(id, self.opts).as_bytes());
+        }
+        let _ = stdout.queue(cursor::Show).and_then(Write::flush);
+    }
+}
+
+#[cfg(unix)]
+mod interrupt {
+    use super::INTERRUPTED;
+    use std::sync::atomic::Ordering;
+
+    extern "C" fn handle(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+    }
+
+    pub fn catch() {
+        // SAFETY: the handler only stores to an atomic, which is
+        // async-signal-safe.
+        unsafe { libc::signal(libc::SIGINT, handle as *const () as libc::sighandler_t) };
+    }
+
+    pub fn release() {
+        // SAFETY: restoring the default action has no preconditions.
+        unsafe { libc::signal(libc::SIGINT, libc::SIG_DFL) };
+    }
+}
+
+#[cfg(windows)]
+mod interrupt {
+    use super::INTERRUPTED;
+    use std::sync::atomic::Ordering;
+    use windows_sys::Win32::System::Console::{
+        CTRL_BREAK_EVENT, CTRL_C_EVENT, SetConsoleCtrlHandler,
+    };
+    use windows_sys::core::BOOL;
+
+    /// Runs on a thread of its own; the events it does not handle go on to
+    /// the default handler, which ends the process.
+    unsafe extern "system" fn handle(event: u32) -> BOOL {
+        match event {
+            CTRL_C_EVENT | CTRL_BREAK_EVENT => {
+                INTERRUPTED.store(true, Ordering::Relaxed);
+                1
+            }
+            _ => 0,
+        }
+    }
+
+    pub fn catch() {
+        // SAFETY: the handler only stores to an atomic.
+        unsafe { SetConsoleCtrlHandler(Some(handle), 1) };
+    }
+
+    pub fn release() {
+        // SAFETY: removing a handler that was added has no preconditions.
+        unsafe { SetConsoleCtrlHandler(Some(handle), 0) };
+    }
+}
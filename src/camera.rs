@@ -0,0 +1,164 @@
+use crate::error::Result;
+use crate::input::Source;
+use image::{DynamicImage, ImageFormat};
+use std::path::Path;
+
+/// Extensions of the camera RAW formats, which share their magic numbers
+/// with TIFF or have none that stands out.
+pub const EXTENSIONS: [&str; 20] = [
+    "3fr", "arw", "cr2", "crw", "dcr", "dng", "erf", "iiq", "kdc", "mos", "mrw", "nef", "nrw",
+    "orf", "pef", "raf", "rw2", "sr2", "srf", "srw",
+];
+
+/// Whether the input could be a camera RAW file: most of them are TIFF files
+/// underneath, and the rest look like no format at all.
+pub fn maybe_raw(format: Option<ImageFormat>) -> bool {
+    cfg!(feature = "camera-raw") && matches!(format, None | Some(ImageFormat::Tiff))
+}
+
+/// Develops a camera RAW file: the sensor data is white balanced with the
+/// camera's own coefficients, demosaiced by merging every block of the color
+/// filter into one pixel, and converted to sRGB. `None`, with the source
+/// rewound, when the data is not a RAW file after all.
+#[cfg(feature = "camera-raw")]
+pub fn decode(source: &mut Source, input: &Path, rotate: bool) -> Result<Option<DynamicImage>> {
+    use crate::error::Error;
+    use image::metadata::Orientation;
+    use image::{Rgb, RgbImage};
+    use rawloader::{Orientation as RawOrientation, RawImageData};
+    use std::io::{Seek, SeekFrom};
+
+    /// XYZ to linear sRGB, both with the D65 white point.
+    const XYZ_TO_SRGB: [[f32; 3]; 3] = [
+        [3.240_454_2, -1.537_138_5, -0.498_531_4],
+        [-0.969_266, 1.876_010_8, 0.041_556],
+        [0.055_643_4, -0.204_025_9, 1.057_225_2],
+    ];
+
+    let raw = match rawloader::decode(source) {
+        Ok(raw) => raw,
+        Err(e) => {
+            log::debug!("{} is not a camera RAW file: {e}", input.display());
+            source
+                .seek(SeekFrom::Start(0))
+                .map_err(|e| Error::open(input, e))?;
+            return Ok(None);
+        }
+    };
+    log::info!(
+        "developing {} {} RAW, CFA {}",
+        raw.clean_make,
+        raw.clean_model,
+        raw.cfa.name
+    );
+
+    let [top, right, bottom, left] = raw.crops;
+    let width = raw.width.saturating_sub(left + right);
+    let height = raw.height.saturating_sub(top + bottom);
+    let sample = |row: usize, col: usize, k: usize| {
+        let i = ((top + row) * raw.width + left + col) * raw.cpp + k;
+        match &raw.data {
+            RawImageData::Integer(data) => data[i] as f32,
+            RawImageData::Float(data) => data[i],
+        }
+    };
+    // Sensor values to 0-1, with the fourth color of CMYG-like filters
+    // counted as green.
+    let level = |v: f32, c: usize| {
+        let c = if c == 3 { 1 } else { c };
+        let (black, white) = (raw.blacklevels[c] as f32, raw.whitelevels[c] as f32);
+        ((v - black) / (white - black).max(1.0)).max(0.0)
+    };
+    let wb = if raw.wb_coeffs[..3].iter().all(|c| c.is_finite() && *c > 0.0) {
+        raw.wb_coeffs
+    } else {
+        raw.neutralwb()
+    };
+    let wb = [wb[0] / wb[1], 1.0, wb[2] / wb[1]];
+    let matrix = raw
+        .xyz_to_cam
+        .iter()
+        .flatten()
+        .any(|v| *v != 0.0)
+        .then(|| raw.cam_to_xyz_normalized());
+
+    // One pixel per repetition of a Bayer filter, or per quarter of an
+    // X-Trans one, which holds every color just as well.
+    let cfa = raw.cropped_cfa();
+    let block = if raw.cpp == 3 {
+        1
+    } else if cfa.width % 3 == 0 {
+        3
+    } else {
+        2
+    };
+    let (w, h) = (width / block, height / block);
+    if w == 0 || h == 0 {
+        return Ok(None);
+    }
+    let img = RgbImage::from_fn(w as u32, h as u32, |x, y| {
+        let (x, y) = (x as usize * block, y as usize * block);
+        let mut sums = [0.0; 3];
+        let mut counts = [0u32; 3];
+        for row in y..y + block {
+            for col in x..x + block {
+                if raw.cpp == 3 {
+                    for (c, sum) in sums.iter_mut().enumerate() {
+                        *sum += level(sample(row, col, c), c);
+                        counts[c] += 1;
+                    }
+                } else {
+                    let c = cfa.color_at(row, col);
+                    let v = level(sample(row, col, 0), c);
+                    let c = if c == 3 { 1 } else { c };
+                    sums[c] += v;
+                    counts[c] += 1;
+                }
+            }
+        }
+        // A monochrome sensor leaves colors out; they get the brightness.
+        let mean = sums.iter().sum::<f32>() / counts.iter().sum::<u32>().max(1) as f32;
+        let cam: [f32; 3] = std::array::from_fn(|c| match counts[c] {
+            0 => mean,
+            n => sums[c] / n as f32 * wb[c],
+        });
+        let linear = match &matrix {
+            Some(m) => {
+                let xyz: [f32; 3] = std::array::from_fn(|i| (0..3).map(|j| m[i][j] * cam[j]).sum());
+                std::array::from_fn(|i| (0..3).map(|j| XYZ_TO_SRGB[i][j] * xyz[j]).sum())
+            }
+            None => cam,
+        };
+        Rgb(linear.map(|v: f32| {
+            let v = v.clamp(0.0, 1.0);
+            let v = if v <= 0.003_130_8 {
+                v * 12.92
+            } else {
+                1.055 * v.powf(1.0 / 2.4) - 0.055
+            };
+            (v * 255.0).round() as u8
+        }))
+    });
+
+    let mut img = DynamicImage::ImageRgb8(img);
+    let orientation = match raw.orientation {
+        RawOrientation::HorizontalFlip => Orientation::FlipHorizontal,
+        RawOrientation::Rotate180 => Orientation::Rotate180,
+        RawOrientation::VerticalFlip => Orientation::FlipVertical,
+        RawOrientation::Transpose => Orientation::Rotate90FlipH,
+        RawOrientation::Rotate90 => Orientation::Rotate90,
+        RawOrientation::Transverse => Orientation::Rotate270FlipH,
+        RawOrientation::Rotate270 => Orientation::Rotate270,
+        RawOrientation::Normal | RawOrientation::Unknown => Orientation::NoTransforms,
+    };
+    if rotate && orientation != Orientation::NoTransforms {
+        log::info!("applying RAW orientation {orientation:?}");
+        img.apply_orientation(orientation);
+    }
+    Ok(Some(img))
+}
+
+#[cfg(not(feature = "camera-raw"))]
+pub fn decode(_: &mut Source, _: &Path, _: bool) -> Result<Option<DynamicImage>> {
+    Ok(None)
+}
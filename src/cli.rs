@@ -0,0 +1,875 @@
+use crate::dicom;
+use crate::fit::{Filter, FitMode, Resize};
+use crate::input::Raw;
+use crate::options::ColorChoice;
+use crate::render::{
+    AnsiPalette, Background, Channel, Charset, ColorDepth, Crop, Deficiency, Dither, Edges,
+    Grayscale, Levels, Palette, Position, RenderMode, ThresholdMode, Tiles, Tonemap,
+};
+use clap::builder::{BoolishValueParser, PossibleValuesParser, TypedValueParser};
+use clap::{ArgAction, Args, CommandFactory, Parser, Subcommand, ValueHint};
+use clap_complete::Shell;
+use serde::Deserialize;
+use std::env;
+use std::ffi::OsString;
+use std::num::{NonZeroU16, NonZeroU32, NonZeroUsize};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "climg",
+    version,
+    about = "Display images in the terminal using braille characters",
+    arg_required_else_help = true
+)]
+pub struct Cli {
+    /// Read default options from this file instead of
+    /// $XDG_CONFIG_HOME/climg/config.toml
+    #[arg(long, global = true, env = "CLIMG_CONFIG", value_name = "PATH", value_hint = ValueHint::FilePath)]
+    pub config: Option<PathBuf>,
+
+    /// Apply the options of a `[preset.<NAME>]` table from the config file
+    #[arg(short, long, global = true, env = "CLIMG_PRESET", value_name = "NAME")]
+    pub preset: Option<String>,
+
+    /// Print diagnostics to stderr (-v for info, -vv for debug, -vvv for trace)
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+impl Cli {
+    /// Parses the process arguments, treating `climg <input> [OPTIONS]` as
+    /// `climg view <input> [OPTIONS]` so the plain invocation keeps working.
+    pub fn parse_with_default_command() -> Self {
+        let mut args: Vec<OsString> = env::args_os().collect();
+        if let Some(first) = args.get(1).and_then(|arg| arg.to_str()) {
+            let cmd = Cli::command();
+            let is_command =
+                first == "help" || cmd.get_subcommands().any(|sub| sub.get_name() == first);
+            let is_top_level_flag = matches!(first, "-h" | "--help" | "-V" | "--version");
+            if !is_command && !is_top_level_flag {
+                args.insert(1, "view".into());
+            }
+        }
+        Cli::parse_from(args)
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Display an image in the terminal (the default)
+    View(ViewArgs),
+    /// Render an image and write the result to a file
+    Convert(ConvertArgs),
+    /// Print image metadata
+    Info(InfoArgs),
+    /// Play an animated image in place
+    Play(PlayArgs),
+    /// Print a shell completion script to stdout
+    Completions(CompletionsArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ViewArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    /// Print a `==> <path> <==` line above each image
+    #[arg(long)]
+    pub header: bool,
+
+    /// Print this line between consecutive images
+    #[arg(long, value_name = "LINE", allow_hyphen_values = true)]
+    pub separator: Option<String>,
+
+    /// Play animated GIFs, PNGs and WebPs this many times when writing to a
+    /// terminal, 0 for forever (other outputs get the first frame) [default:
+    /// as many times as the file says]
+    #[arg(long = "loop", value_name = "N")]
+    pub loops: Option<u32>,
+
+    /// Draw a row of small braille previews at thresholds around the
+    /// automatic one, each labeled with its value, to help pick --threshold
+    #[arg(long)]
+    pub sweep: bool,
+
+    #[command(flatten)]
+    pub render: RenderArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ConvertArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    /// File to write the rendered text to [default: <input>.txt]. Only
+    /// allowed with a single input
+    #[arg(short, long, value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub render: RenderArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct InfoArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+}
+
+/// The images a command works on, given directly, as wildcard patterns or
+/// found by walking directories.
+#[derive(Debug, Args)]
+pub struct InputArgs {
+    /// Paths or wildcard patterns of the images, processed one after another;
+    /// `-` reads an image from the standard input, `data:` URIs carry the
+    /// image itself, and with the `http` feature http:// and https:// URLs
+    /// are downloaded
+    #[arg(required_unless_present = "recursive", value_hint = ValueHint::FilePath)]
+    pub inputs: Vec<PathBuf>,
+
+    /// Also process every image found in this directory and its subdirectories
+    #[arg(short, long, value_name = "DIR", value_hint = ValueHint::DirPath)]
+    pub recursive: Vec<PathBuf>,
+
+    /// Descend at most this many levels below a --recursive directory (1 only
+    /// looks at the files directly inside it)
+    #[arg(long, value_name = "N", requires = "recursive")]
+    pub max_depth: Option<NonZeroUsize>,
+
+    /// Only pick up files with these extensions in --recursive directories
+    /// [default: every format climg can decode]
+    #[arg(
+        long = "ext",
+        value_name = "EXT",
+        value_delimiter = ',',
+        requires = "recursive"
+    )]
+    pub extensions: Vec<String>,
+
+    /// Read the inputs as raw pixels of this size and format (rgb, rgba or
+    /// gray) instead of image files, e.g. `640x480:rgba` for frames piped
+    /// from ffmpeg [default format: rgb]
+    #[arg(long, value_name = "WxH[:FORMAT]")]
+    pub raw: Option<Raw>,
+
+    /// Page of PDF and multi-page TIFF inputs, or frame of DICOM ones, to draw,
+    /// counted from 1
+    #[arg(long, value_name = "N", default_value = "1")]
+    pub page: NonZeroUsize,
+
+    /// Of ICO files holding several sizes, draw the smallest image at least
+    /// N pixels wide or high [default: the largest]
+    #[arg(long, value_name = "N")]
+    pub icon_size: Option<NonZeroU32>,
+}
+
+#[derive(Debug, Args)]
+pub struct PlayArgs {
+    /// Path to the animation to play, or `-` to read it from the standard
+    /// input
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub input: PathBuf,
+
+    /// Read the input as a stream of raw frames of this size and format
+    /// (rgb, rgba or gray) and draw each as soon as it arrives, e.g.
+    /// `640x480:rgba` from ffmpeg [default format: rgb]
+    #[arg(long, value_name = "WxH[:FORMAT]")]
+    pub raw: Option<Raw>,
+
+    /// Play the animation this many times, 0 for forever [default: as many
+    /// times as the file says]
+    #[arg(long = "loop", value_name = "N", conflicts_with = "raw")]
+    pub loops: Option<u32>,
+
+    #[command(flatten)]
+    pub render: RenderArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct CompletionsArgs {
+    /// Shell to generate completions for
+    #[arg(value_enum)]
+    pub shell: Shell,
+}
+
+/// Options shared by every subcommand that renders an image.
+///
+/// Every field is optional so that the same struct can be read from the
+/// config file and layered under the command line, see [`RenderArgs::merge`].
+/// Each option can also be set through a `CLIMG_<NAME>` environment
+/// variable, which takes precedence over the config file but not over an
+/// explicit flag.
+#[derive(Debug, Clone, Default, Args, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RenderArgs {
+    /// Characters or graphics protocol to draw the image with [default: auto]
+    #[arg(short, long, env = "CLIMG_MODE", value_enum)]
+    pub mode: Option<RenderMode>,
+
+    /// Light up the dark parts of the image instead of the bright ones (in
+    /// color modes: invert the colors)
+    #[arg(
+        short,
+        long,
+        env = "CLIMG_INVERT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub invert: Option<bool>,
+
+    /// Unless --invert is given, ask the terminal for its background color
+    /// and invert the monochrome modes on light backgrounds so the lit
+    /// characters stand for the bright parts [default: true]
+    #[arg(
+        long,
+        env = "CLIMG_AUTO_INVERT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub auto_invert: Option<bool>,
+
+    /// Fixed brightness cutoff (0-255) used instead of the automatic Otsu threshold
+    #[arg(short, long, env = "CLIMG_THRESHOLD", value_name = "0-255")]
+    pub threshold: Option<u8>,
+
+    /// How colors are turned into brightness for the monochrome modes
+    /// [default: luminosity]
+    #[arg(long = "gray", env = "CLIMG_GRAY", value_enum, value_name = "METHOD")]
+    pub grayscale: Option<Grayscale>,
+
+    /// Draw the outlines found with this method with braille dots instead
+    /// of the brightness, like line art in a coloring book [default method:
+    /// sobel]
+    #[arg(
+        long,
+        env = "CLIMG_EDGES",
+        value_enum,
+        value_name = "METHOD",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "sobel"
+    )]
+    pub edges: Option<Edges>,
+
+    /// Edge strength (0-255) above which --edges canny keeps the edges that
+    /// touch stronger ones [default: 20]
+    #[arg(long, env = "CLIMG_EDGE_LO", value_name = "0-255")]
+    pub edge_lo: Option<u8>,
+
+    /// Edge strength (0-255) above which --edges canny always keeps an edge
+    /// [default: 50]
+    #[arg(long, env = "CLIMG_EDGE_HI", value_name = "0-255")]
+    pub edge_hi: Option<u8>,
+
+    /// Draw the image as a relief lit from this angle in degrees, 0 being
+    /// from the right and 90 from the top, with the braille modes
+    /// [default angle: 135]
+    #[arg(
+        long,
+        env = "CLIMG_EMBOSS",
+        value_name = "ANGLE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "135",
+        allow_negative_numbers = true,
+        value_parser = degrees
+    )]
+    pub emboss: Option<f32>,
+
+    /// Turn photos into pen-and-ink line art with the braille modes, with an
+    /// extended difference of Gaussians
+    #[arg(
+        long,
+        env = "CLIMG_SKETCH",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub sketch: Option<bool>,
+
+    /// Brightness from 0 to 1 below which --sketch fills areas with ink as
+    /// well; 0 only draws the outlines [default: 0]
+    #[arg(long, env = "CLIMG_SKETCH_THRESHOLD", value_name = "0-1", value_parser = unit_interval)]
+    pub sketch_threshold: Option<f32>,
+
+    /// How abruptly the lines of --sketch fade out; higher values draw
+    /// crisper, thinner lines [default: 10]
+    #[arg(long, env = "CLIMG_SKETCH_SHARPNESS", value_name = "SHARPNESS", value_parser = positive)]
+    pub sketch_sharpness: Option<f32>,
+
+    /// Pick the threshold so that this percentage of the pixels is lit,
+    /// which is more predictable than Otsu for logos and QR-like images
+    #[arg(
+        long,
+        env = "CLIMG_THRESHOLD_PERCENTILE",
+        value_name = "0-100",
+        value_parser = percentage
+    )]
+    pub threshold_percentile: Option<f32>,
+
+    /// How the threshold is picked when --threshold does not fix it
+    /// [default: otsu]
+    #[arg(long, env = "CLIMG_THRESHOLD_MODE", value_enum, value_name = "MODE")]
+    pub threshold_mode: Option<ThresholdMode>,
+
+    /// Size in pixels of the window the adaptive, sauvola and niblack
+    /// threshold modes look at (a braille character is 2x4 pixels)
+    /// [default: 15]
+    #[arg(long, env = "CLIMG_THRESHOLD_WINDOW", value_name = "PIXELS")]
+    pub threshold_window: Option<NonZeroU16>,
+
+    /// Subtracted from the local mean by --threshold-mode adaptive: positive
+    /// values keep flat areas lit and dark details such as text unlit,
+    /// negative ones do the opposite [default: 10]
+    #[arg(
+        long,
+        env = "CLIMG_THRESHOLD_OFFSET",
+        value_name = "N",
+        allow_negative_numbers = true
+    )]
+    pub threshold_offset: Option<i16>,
+
+    /// How strongly the local contrast moves the threshold of
+    /// --threshold-mode sauvola and niblack [default: 0.2 for sauvola, -0.2
+    /// for niblack]
+    #[arg(
+        long,
+        env = "CLIMG_THRESHOLD_K",
+        value_name = "K",
+        allow_negative_numbers = true
+    )]
+    pub threshold_k: Option<f32>,
+
+    /// Columns and rows of the grid --threshold-mode tiled-otsu picks a
+    /// threshold for each cell of [default: 4x4]
+    #[arg(long, env = "CLIMG_THRESHOLD_TILES", value_name = "COLSxROWS")]
+    pub threshold_tiles: Option<Tiles>,
+
+    /// Hysteresis thresholding: pixels at least this bright are lit, and
+    /// so are those above --threshold-lo that touch lit ones, which cleans up
+    /// noisy photos and line drawings
+    #[arg(
+        long,
+        env = "CLIMG_THRESHOLD_HI",
+        value_name = "0-255",
+        requires = "threshold_lo"
+    )]
+    pub threshold_hi: Option<u8>,
+
+    /// The lower cutoff of hysteresis thresholding (see --threshold-hi)
+    #[arg(
+        long,
+        env = "CLIMG_THRESHOLD_LO",
+        value_name = "0-255",
+        requires = "threshold_hi"
+    )]
+    pub threshold_lo: Option<u8>,
+
+    /// Ignore the EXIF orientation of photos instead of turning them upright
+    #[arg(
+        long = "no-exif-rotate",
+        env = "CLIMG_EXIF_ROTATE",
+        num_args = 0,
+        default_missing_value = "false",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub exif_rotate: Option<bool>,
+
+    /// Rotate the image clockwise by this many degrees: 90, 180 and 270 are
+    /// exact, other angles widen the canvas to fit the whole image
+    #[arg(
+        long,
+        env = "CLIMG_ROTATE",
+        value_name = "DEGREES",
+        allow_negative_numbers = true,
+        value_parser = degrees
+    )]
+    pub rotate: Option<f32>,
+
+    /// Mirror the image left to right, e.g. for webcam frames
+    #[arg(
+        long,
+        env = "CLIMG_FLIP_H",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub flip_h: Option<bool>,
+
+    /// Mirror the image top to bottom
+    #[arg(
+        long,
+        env = "CLIMG_FLIP_V",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub flip_v: Option<bool>,
+
+    /// Only draw this region of the image, given in pixels or percentages,
+    /// e.g. `0,0,50%,50%` for the top left quarter
+    #[arg(long, env = "CLIMG_CROP", value_name = "X,Y,W,H")]
+    pub crop: Option<Crop>,
+
+    /// Only draw this channel, as grayscale, e.g. `a` to inspect an alpha
+    /// mask
+    #[arg(long, env = "CLIMG_CHANNEL", value_enum, value_name = "CHANNEL")]
+    pub channel: Option<Channel>,
+
+    /// Draw this image over the main one, e.g. a watermark, a logo or a mask
+    #[arg(long, env = "CLIMG_OVERLAY", value_name = "PATH", value_hint = ValueHint::FilePath)]
+    pub overlay: Option<PathBuf>,
+
+    /// Where --overlay goes, in pixels from the top left corner or in
+    /// percentages, `100%,100%` being the bottom right corner [default: 0,0]
+    #[arg(long, env = "CLIMG_OVERLAY_POS", value_name = "X,Y")]
+    pub overlay_pos: Option<Position>,
+
+    /// Opacity of --overlay from 0 to 1 [default: 1]
+    #[arg(long, env = "CLIMG_OVERLAY_ALPHA", value_name = "0-1", value_parser = unit_interval)]
+    pub overlay_alpha: Option<f32>,
+
+    /// How HDR images such as OpenEXR and Radiance renders are brought into
+    /// the displayable range: `reinhard` keeps every highlight, `aces` has
+    /// the contrast of film and `clamp` cuts off everything brighter than
+    /// white [default: reinhard]
+    #[arg(long, env = "CLIMG_TONEMAP", value_enum, value_name = "OPERATOR")]
+    pub tonemap: Option<Tonemap>,
+
+    /// Brighten (positive) or darken (negative) HDR images by this many stops
+    /// before --tonemap [default: 0]
+    #[arg(
+        long,
+        env = "CLIMG_EXPOSURE",
+        value_name = "STOPS",
+        allow_negative_numbers = true
+    )]
+    pub exposure: Option<f32>,
+
+    /// Show this range of stored values of DICOM images from black to white,
+    /// e.g. `40,400` for soft tissue or `-600,1500` for lungs [default: the
+    /// window the file suggests]
+    #[arg(
+        long,
+        env = "CLIMG_DICOM_WINDOW",
+        value_name = "CENTER,WIDTH",
+        allow_hyphen_values = true
+    )]
+    pub dicom_window: Option<dicom::Window>,
+
+    /// Map this input range to the full range before rendering, like the
+    /// Levels tool of image editors, e.g. `40,200` to turn the gray
+    /// background of a scan white
+    #[arg(long, env = "CLIMG_LEVELS", value_name = "BLACK,WHITE")]
+    pub levels: Option<Levels>,
+
+    /// Make the image brighter (up to 100) or darker (down to -100) before
+    /// rendering [default: 0]
+    #[arg(
+        long,
+        env = "CLIMG_BRIGHTNESS",
+        value_name = "-100..100",
+        allow_negative_numbers = true,
+        value_parser = clap::value_parser!(i8).range(-100..=100)
+    )]
+    pub brightness: Option<i8>,
+
+    /// Raise (up to 100) or lower (down to -100) the contrast of the image
+    /// before rendering [default: 0]
+    #[arg(
+        long,
+        env = "CLIMG_CONTRAST",
+        value_name = "-100..100",
+        allow_negative_numbers = true,
+        value_parser = clap::value_parser!(i8).range(-100..=100)
+    )]
+    pub contrast: Option<i8>,
+
+    /// Scale the saturation of the colors before rendering: 0 is gray, above
+    /// 1 more vivid, which keeps images readable at --colors 256 or 16
+    /// [default: 1]
+    #[arg(long, env = "CLIMG_SATURATION", value_name = "FACTOR", value_parser = non_negative)]
+    pub saturation: Option<f32>,
+
+    /// Turn the hues of the colors by this many degrees before rendering
+    /// [default: 0]
+    #[arg(
+        long,
+        env = "CLIMG_HUE_ROTATE",
+        value_name = "DEGREES",
+        allow_negative_numbers = true,
+        value_parser = degrees
+    )]
+    pub hue_rotate: Option<f32>,
+
+    /// Blur the image with a Gaussian of this standard deviation in pixels
+    /// before rendering, which keeps noise from turning into stray dots
+    #[arg(long, env = "CLIMG_BLUR", value_name = "SIGMA", value_parser = positive)]
+    pub blur: Option<f32>,
+
+    /// Sharpen the image by this amount (1 doubles the fine detail) before
+    /// rendering, which helps small text survive the downscale
+    #[arg(long, env = "CLIMG_SHARPEN", value_name = "AMOUNT", value_parser = positive)]
+    pub sharpen: Option<f32>,
+
+    /// Reduce every color channel to this many levels before rendering, for
+    /// a flat color look with less banding noise at small palettes
+    #[arg(
+        long,
+        env = "CLIMG_POSTERIZE",
+        value_name = "LEVELS",
+        value_parser = clap::value_parser!(u8).range(2..)
+    )]
+    pub posterize: Option<u8>,
+
+    /// Show the image the way people with this color vision deficiency see
+    /// it
+    #[arg(long, env = "CLIMG_SIMULATE", value_enum, value_name = "DEFICIENCY")]
+    pub simulate: Option<Deficiency>,
+
+    /// Remove specks of noise that would turn into stray dots with a median
+    /// filter of this radius in pixels before thresholding [default radius: 1]
+    #[arg(
+        long,
+        env = "CLIMG_DENOISE",
+        value_name = "RADIUS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "1",
+        value_parser = clap::value_parser!(u32).range(1..=16)
+    )]
+    pub denoise: Option<u32>,
+
+    /// Stretch the brightness so the darkest pixels become black and the
+    /// brightest white before thresholding, ignoring this percentage of the
+    /// pixels at either end, which rescues washed-out photos
+    #[arg(
+        long,
+        env = "CLIMG_AUTOCONTRAST",
+        value_name = "CUTOFF%",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "0",
+        value_parser = percentage
+    )]
+    pub autocontrast: Option<f32>,
+
+    /// Equalize the histogram before thresholding, which brings out detail
+    /// in both the shadows and the highlights of low contrast images
+    #[arg(
+        long,
+        env = "CLIMG_EQUALIZE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub equalize: Option<bool>,
+
+    /// Equalize the histogram of every tile of a grid (see --clahe-tiles)
+    /// before thresholding, limiting how much any brightness is boosted to
+    /// CLIP times its fair share, which brings out both bright skies and dark
+    /// foregrounds [default clip: 2]
+    #[arg(
+        long,
+        env = "CLIMG_CLAHE",
+        value_name = "CLIP",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "2",
+        value_parser = positive
+    )]
+    pub clahe: Option<f32>,
+
+    /// Columns and rows of the grid of --clahe [default: 8x8]
+    #[arg(long, env = "CLIMG_CLAHE_TILES", value_name = "COLSxROWS")]
+    pub clahe_tiles: Option<Tiles>,
+
+    /// Gamma correction before thresholding: values above 1 brighten the
+    /// midtones, values below 1 darken them [default: 1]
+    #[arg(long, env = "CLIMG_GAMMA", value_name = "GAMMA", value_parser = positive)]
+    pub gamma: Option<f32>,
+
+    /// What transparent pixels are drawn against: `transparent` to let the
+    /// terminal background show through, or a color such as `white` or
+    /// `#1e1e2e` to blend them onto [default: transparent]
+    #[arg(long, env = "CLIMG_BG", value_name = "COLOR")]
+    pub bg: Option<Background>,
+
+    /// Draw transparent pixels of the color modes over a gray checkerboard,
+    /// like image editors do, to tell them apart from white or black ones
+    #[arg(
+        long,
+        env = "CLIMG_CHECKER",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub checker: Option<bool>,
+
+    /// How the braille modes turn brightness into dots: `none` compares every
+    /// pixel to the threshold, the others spread the difference around to
+    /// keep gradients [default: none]
+    #[arg(long, env = "CLIMG_DITHER", value_enum, value_name = "METHOD")]
+    pub dither: Option<Dither>,
+
+    /// Size of the threshold matrix of --dither bayer; larger matrices give
+    /// more shades but coarser patterns [default: 4]
+    #[arg(
+        long,
+        env = "CLIMG_BAYER_SIZE",
+        value_name = "SIZE",
+        value_parser = PossibleValuesParser::new(["2", "4", "8", "16"])
+            .map(|size| size.parse::<u32>().expect("the possible values are numbers"))
+    )]
+    pub bayer_size: Option<u32>,
+
+    /// Scan every other row from right to left when diffusing the error
+    /// (--dither floyd-steinberg or atkinson, --color-dither), which avoids
+    /// the drift plain left to right scanning shows on large flat areas
+    #[arg(
+        long,
+        env = "CLIMG_SERPENTINE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub serpentine: Option<bool>,
+
+    /// How much of the error or noise the dithering applies, from 0 (plain
+    /// threshold or nearest color) to 1 (fully dithered) [default: 1]
+    #[arg(long, env = "CLIMG_DITHER_STRENGTH", value_name = "0-1", value_parser = unit_interval)]
+    pub dither_strength: Option<f32>,
+
+    /// Seed of the noise of --dither random; the same seed gives the same
+    /// output [default: a different one every run]
+    #[arg(long, env = "CLIMG_SEED", value_name = "N")]
+    pub seed: Option<u64>,
+
+    /// Output width in terminal columns (defaults to the terminal width)
+    #[arg(short = 'W', long, env = "CLIMG_WIDTH", value_name = "COLS")]
+    pub width: Option<NonZeroU16>,
+
+    /// Output height in terminal rows (defaults to the terminal height)
+    #[arg(short = 'H', long, env = "CLIMG_HEIGHT", value_name = "ROWS")]
+    pub height: Option<NonZeroU16>,
+
+    /// How to scale the image into the output grid [default: contain, or
+    /// stretch when both --width and --height are given]
+    #[arg(short, long, env = "CLIMG_FIT", value_enum)]
+    pub fit: Option<FitMode>,
+
+    /// With --fit cover, cut the overflow where the image has the least
+    /// detail instead of evenly from both sides
+    #[arg(
+        long,
+        env = "CLIMG_SMART_CROP",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub smart_crop: Option<bool>,
+
+    /// How --fit stretch and cover change the aspect ratio: `scale` distorts
+    /// or crops the image, `seam` removes its least detailed seams of pixels
+    /// first [default: scale]
+    #[arg(long, env = "CLIMG_RESIZE", value_enum, value_name = "METHOD")]
+    pub resize: Option<Resize>,
+
+    /// How pixels are interpolated when the image is scaled: `nearest` keeps
+    /// pixel art crisp, `lanczos3` is the sharpest but can ring around text
+    /// [default: lanczos3]
+    #[arg(long, env = "CLIMG_FILTER", value_enum, value_name = "FILTER")]
+    pub filter: Option<Filter>,
+
+    /// Characters used by --mode ascii, from the emptiest to the densest
+    /// [default: " .:-=+*#%@"]
+    #[arg(
+        long,
+        env = "CLIMG_CHARSET",
+        value_name = "CHARS",
+        allow_hyphen_values = true
+    )]
+    pub charset: Option<Charset>,
+
+    /// Maximum number of palette colors used by --mode sixel [default: 256]
+    #[arg(
+        long,
+        env = "CLIMG_SIXEL_COLORS",
+        value_name = "2-256",
+        value_parser = clap::value_parser!(u16).range(2..=256)
+    )]
+    pub sixel_colors: Option<u16>,
+
+    /// When the color text modes may use colors; without them they draw
+    /// monochrome braille. NO_COLOR disables colors in auto [default: auto]
+    #[arg(long, env = "CLIMG_COLOR", value_name = "WHEN", value_enum)]
+    pub color: Option<ColorChoice>,
+
+    /// Colors the color text modes may use, for terminals without truecolor
+    /// [default: truecolor]
+    #[arg(long, env = "CLIMG_COLORS", value_enum)]
+    pub colors: Option<ColorDepth>,
+
+    /// Dither the image to the colors allowed by --colors instead of
+    /// snapping every cell to the nearest one
+    #[arg(
+        long,
+        env = "CLIMG_COLOR_DITHER",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "true",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub color_dither: Option<bool>,
+
+    /// What the terminal's 16 standard colors look like, for --colors 16: 16
+    /// comma separated #rrggbb values, black to white and then the bright
+    /// variants [default: xterm's colors]
+    #[arg(long, env = "CLIMG_ANSI_COLORS", value_name = "COLORS")]
+    pub ansi_colors: Option<AnsiPalette>,
+
+    /// Snap the colors of the color text modes to a terminal theme's palette
+    #[arg(long, env = "CLIMG_PALETTE", value_enum)]
+    pub palette: Option<Palette>,
+
+    /// Snap the colors to a palette read from a GIMP .gpl file or a file with
+    /// one #rrggbb color per line; overrides --palette
+    #[arg(long, env = "CLIMG_PALETTE_FILE", value_name = "PATH", value_hint = ValueHint::FilePath)]
+    pub palette_file: Option<PathBuf>,
+}
+
+impl RenderArgs {
+    /// Layers `other` on top of `self`: every option set in `other` wins.
+    pub fn merge(self, other: RenderArgs) -> RenderArgs {
+        RenderArgs {
+            mode: other.mode.or(self.mode),
+            invert: other.invert.or(self.invert),
+            auto_invert: other.auto_invert.or(self.auto_invert),
+            threshold: other.threshold.or(self.threshold),
+            grayscale: other.grayscale.or(self.grayscale),
+            edges: other.edges.or(self.edges),
+            edge_lo: other.edge_lo.or(self.edge_lo),
+            edge_hi: other.edge_hi.or(self.edge_hi),
+            emboss: other.emboss.or(self.emboss),
+            sketch: other.sketch.or(self.sketch),
+            sketch_threshold: other.sketch_threshold.or(self.sketch_threshold),
+            sketch_sharpness: other.sketch_sharpness.or(self.sketch_sharpness),
+            threshold_percentile: other.threshold_percentile.or(self.threshold_percentile),
+            threshold_mode: other.threshold_mode.or(self.threshold_mode),
+            threshold_window: other.threshold_window.or(self.threshold_window),
+            threshold_offset: other.threshold_offset.or(self.threshold_offset),
+            threshold_k: other.threshold_k.or(self.threshold_k),
+            threshold_tiles: other.threshold_tiles.or(self.threshold_tiles),
+            threshold_hi: other.threshold_hi.or(self.threshold_hi),
+            threshold_lo: other.threshold_lo.or(self.threshold_lo),
+            exif_rotate: other.exif_rotate.or(self.exif_rotate),
+            rotate: other.rotate.or(self.rotate),
+            flip_h: other.flip_h.or(self.flip_h),
+            flip_v: other.flip_v.or(self.flip_v),
+            crop: other.crop.or(self.crop),
+            channel: other.channel.or(self.channel),
+            overlay: other.overlay.or(self.overlay),
+            overlay_pos: other.overlay_pos.or(self.overlay_pos),
+            overlay_alpha: other.overlay_alpha.or(self.overlay_alpha),
+            tonemap: other.tonemap.or(self.tonemap),
+            exposure: other.exposure.or(self.exposure),
+            dicom_window: other.dicom_window.or(self.dicom_window),
+            levels: other.levels.or(self.levels),
+            brightness: other.brightness.or(self.brightness),
+            contrast: other.contrast.or(self.contrast),
+            saturation: other.saturation.or(self.saturation),
+            hue_rotate: other.hue_rotate.or(self.hue_rotate),
+            blur: other.blur.or(self.blur),
+            sharpen: other.sharpen.or(self.sharpen),
+            posterize: other.posterize.or(self.posterize),
+            simulate: other.simulate.or(self.simulate),
+            denoise: other.denoise.or(self.denoise),
+            autocontrast: other.autocontrast.or(self.autocontrast),
+            equalize: other.equalize.or(self.equalize),
+            clahe: other.clahe.or(self.clahe),
+            clahe_tiles: other.clahe_tiles.or(self.clahe_tiles),
+            gamma: other.gamma.or(self.gamma),
+            bg: other.bg.or(self.bg),
+            checker: other.checker.or(self.checker),
+            dither: other.dither.or(self.dither),
+            bayer_size: other.bayer_size.or(self.bayer_size),
+            serpentine: other.serpentine.or(self.serpentine),
+            dither_strength: other.dither_strength.or(self.dither_strength),
+            seed: other.seed.or(self.seed),
+            width: other.width.or(self.width),
+            height: other.height.or(self.height),
+            fit: other.fit.or(self.fit),
+            smart_crop: other.smart_crop.or(self.smart_crop),
+            resize: other.resize.or(self.resize),
+            filter: other.filter.or(self.filter),
+            charset: other.charset.or(self.charset),
+            sixel_colors: other.sixel_colors.or(self.sixel_colors),
+            color: other.color.or(self.color),
+            colors: other.colors.or(self.colors),
+            color_dither: other.color_dither.or(self.color_dither),
+            ansi_colors: other.ansi_colors.or(self.ansi_colors),
+            palette: other.palette.or(self.palette),
+            palette_file: other.palette_file.or(self.palette_file),
+        }
+    }
+}
+
+/// Parses a number between 0 and 1.
+fn unit_interval(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(v) if (0.0..=1.0).contains(&v) => Ok(v),
+        Ok(_) => Err("must be between 0 and 1".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn percentage(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(v) if (0.0..=100.0).contains(&v) => Ok(v),
+        Ok(_) => Err("must be between 0 and 100".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn positive(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(v) if v > 0.0 && v.is_finite() => Ok(v),
+        Ok(_) => Err("must be greater than 0".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn non_negative(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(v) if v >= 0.0 && v.is_finite() => Ok(v),
+        Ok(_) => Err("must be 0 or greater".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn degrees(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(v) if v.is_finite() => Ok(v),
+        Ok(_) => Err("must be a finite number".to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
@@ -0,0 +1,81 @@
+use crate::cli::RenderArgs;
+use crate::error::{Error, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Default options read from `config.toml`. Keys use the long flag names,
+/// e.g. `invert = true` or `fit = "cover"`. Named bundles of options live in
+/// `[preset.<name>]` tables and are selected with `--preset <name>`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    #[serde(flatten)]
+    pub render: RenderArgs,
+    pub preset: BTreeMap<String, RenderArgs>,
+}
+
+impl Config {
+    /// Loads `path`, or the file in the default location when no path is
+    /// given. A missing default file is not an error; a missing explicit
+    /// one is.
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let (path, explicit) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if !explicit && e.kind() == io::ErrorKind::NotFound => {
+                log::debug!("no config file at {}", path.display());
+                return Ok(Config::default());
+            }
+            Err(e) => {
+                return Err(Error::Config(format!(
+                    "config file {}: {e}",
+                    path.display()
+                )));
+            }
+        };
+
+        log::debug!("loading config file {}", path.display());
+        toml::from_str(&text)
+            .map_err(|e| Error::Config(format!("config file {}: {e}", path.display())))
+    }
+
+    /// Layers the configured defaults, the selected preset and the command
+    /// line arguments, in increasing order of precedence.
+    pub fn resolve(&self, preset: Option<&str>, args: &RenderArgs) -> Result<RenderArgs> {
+        let mut resolved = self.render.clone();
+        if let Some(name) = preset {
+            let Some(preset) = self.preset.get(name) else {
+                let known: Vec<&str> = self.preset.keys().map(String::as_str).collect();
+                return Err(Error::Config(if known.is_empty() {
+                    format!("unknown preset `{name}` (no presets are configured)")
+                } else {
+                    format!("unknown preset `{name}` (available: {})", known.join(", "))
+                }));
+            };
+            log::debug!("applying preset {name}");
+            resolved = resolved.merge(preset.clone());
+        }
+        Ok(resolved.merge(args.clone()))
+    }
+}
+
+/// `$XDG_CONFIG_HOME/climg/config.toml`, falling back to
+/// `~/.config/climg/config.toml`.
+fn default_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("climg").join("config.toml"))
+}
@@ -0,0 +1,245 @@
+use crate::render::RenderMode;
+use std::env;
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// Picks the best mode the terminal supports, in order of preference:
+/// kitty graphics, sixel, iTerm2 inline images, truecolor half blocks and
+/// finally plain braille. Environment variables are checked first and the
+/// terminal is only queried when they are not conclusive.
+pub fn best_mode() -> RenderMode {
+    let mode = detect();
+    log::info!("detected mode={mode:?}");
+    mode
+}
+
+fn detect() -> RenderMode {
+    if tmux_passthrough() == Some(false) {
+        log::info!("tmux does not pass graphics through, see allow-passthrough");
+        return text_mode();
+    }
+
+    let term = env_var("TERM");
+    let program = env_var("TERM_PROGRAM");
+    if env::var_os("KITTY_WINDOW_ID").is_some()
+        || term == "xterm-kitty"
+        || term == "xterm-ghostty"
+        || matches!(program.as_str(), "ghostty" | "WezTerm")
+    {
+        return RenderMode::Kitty;
+    }
+
+    if let Some(reply) = probe::query(QUERIES).map(|bytes| Reply::parse(&bytes)) {
+        log::debug!("terminal replied {reply:?}");
+        if reply.kitty {
+            return RenderMode::Kitty;
+        }
+        if let Some(name) = &reply.name {
+            let name = name.to_ascii_lowercase();
+            if ["kitty", "ghostty", "wezterm"]
+                .iter()
+                .any(|n| name.contains(n))
+            {
+                return RenderMode::Kitty;
+            }
+        }
+        if reply.sixel {
+            return RenderMode::Sixel;
+        }
+    }
+
+    if program == "iTerm.app" || program == "mintty" || env_var("LC_TERMINAL") == "iTerm2" {
+        return RenderMode::Iterm;
+    }
+    text_mode()
+}
+
+/// The best mode that does not need a graphics protocol.
+fn text_mode() -> RenderMode {
+    if matches!(env_var("COLORTERM").as_str(), "truecolor" | "24bit") {
+        return RenderMode::Blocks;
+    }
+    RenderMode::Braille
+}
+
+/// Whether tmux passes escape sequences through to the outer terminal, or
+/// `None` outside of tmux. tmux 3.3 and later only do so with the
+/// `allow-passthrough` option on; older versions lack the option and always
+/// pass them through.
+pub fn tmux_passthrough() -> Option<bool> {
+    static ALLOWED: OnceLock<Option<bool>> = OnceLock::new();
+    *ALLOWED.get_or_init(|| {
+        env::var_os("TMUX")?;
+        let output = Command::new("tmux")
+            .args(["show-options", "-gv", "allow-passthrough"])
+            .output();
+        let allowed = match output {
+            Ok(output) if output.status.success() => {
+                let value = String::from_utf8_lossy(&output.stdout);
+                matches!(value.trim(), "on" | "all")
+            }
+            _ => true,
+        };
+        log::debug!("inside tmux, passthrough allowed: {allowed}");
+        Some(allowed)
+    })
+}
+
+fn env_var(name: &str) -> String {
+    env::var(name).unwrap_or_default()
+}
+
+/// A kitty graphics query and an XTGETTCAP request for the terminal name.
+const QUERIES: &[u8] = b"\x1b_Gi=31,s=1,v=1,a=q,t=d,f=24;AAAA\x1b\\\x1bP+q544e\x1b\\";
+
+/// Whether the terminal has a light background, which it reports in reply
+/// to OSC 11, or `None` if it does not say.
+pub fn light_background() -> Option<bool> {
+    let reply = probe::query(b"\x1b]11;?\x1b\\")?;
+    let [r, g, b] = parse_osc11(&String::from_utf8_lossy(&reply))?;
+    let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    log::debug!("terminal background luma {luma:.2}");
+    Some(luma > 0.5)
+}
+
+/// Parses `ESC ] 11 ; rgb:<r>/<g>/<b>`, each channel 1 to 4 hex digits,
+/// into intensities between 0 and 1.
+fn parse_osc11(reply: &str) -> Option<[f32; 3]> {
+    let start = reply.find("\x1b]11;rgb:")? + 9;
+    let rest = &reply[start..];
+    let end = rest.find(['\x1b', '\x07']).unwrap_or(rest.len());
+    let mut channels = rest[..end].split('/').map(|hex| {
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        let max = (1u32 << (4 * hex.len().clamp(1, 4))) - 1;
+        Some(value as f32 / max as f32)
+    });
+    Some([channels.next()??, channels.next()??, channels.next()??])
+}
+
+/// What the terminal said about itself.
+#[derive(Debug, Default)]
+struct Reply {
+    /// It answered the kitty graphics query.
+    kitty: bool,
+    /// It listed sixel graphics among its DA1 attributes.
+    sixel: bool,
+    /// The name it reported through XTGETTCAP.
+    name: Option<String>,
+}
+
+impl Reply {
+    fn parse(bytes: &[u8]) -> Reply {
+        let text = String::from_utf8_lossy(bytes);
+        let mut reply = Reply {
+            kitty: text.contains("\x1b_Gi=31;OK"),
+            ..Reply::default()
+        };
+
+        // DA1: ESC [ ? <attr> ; <attr> ... c
+        if let Some(start) = text.find("\x1b[?")
+            && let Some(len) = text[start + 3..].find('c')
+        {
+            reply.sixel = text[start + 3..start + 3 + len]
+                .split(';')
+                .any(|attr| attr == "4");
+        }
+
+        // XTGETTCAP: ESC P 1 + r <hex name> = <hex value> ESC \
+        if let Some(start) = text.find("\x1bP1+r")
+            && let Some(len) = text[start..].find("\x1b\\")
+            && let Some((_, value)) = text[start + 5..start + len].split_once('=')
+        {
+            reply.name = decode_hex(value);
+        }
+        reply
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<String> {
+    let bytes = (0..hex.len() / 2)
+        .map(|i| u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(unix)]
+mod probe {
+    use crossterm::terminal;
+    use std::fs::{File, OpenOptions};
+    use std::io::{IsTerminal, Read, Write};
+    use std::os::fd::AsRawFd;
+    use std::time::{Duration, Instant};
+
+    /// How long to wait for the terminal to answer. Terminals that do not
+    /// understand a query ignore it, but every terminal answers DA1, so the
+    /// wait normally ends much earlier.
+    const TIMEOUT: Duration = Duration::from_millis(200);
+
+    /// Sends `request` followed by DA1 to the terminal and collects the
+    /// replies up to the DA1 answer.
+    pub fn query(request: &[u8]) -> Option<Vec<u8>> {
+        if !std::io::stdin().is_terminal() {
+            return None;
+        }
+        let mut tty = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/tty")
+            .ok()?;
+        terminal::enable_raw_mode().ok()?;
+        let reply = exchange(&mut tty, request);
+        let _ = terminal::disable_raw_mode();
+        reply
+    }
+
+    fn exchange(tty: &mut File, request: &[u8]) -> Option<Vec<u8>> {
+        tty.write_all(request).ok()?;
+        tty.write_all(b"\x1b[c").ok()?;
+        tty.flush().ok()?;
+
+        let deadline = Instant::now() + TIMEOUT;
+        let mut reply = Vec::new();
+        let mut buf = [0u8; 256];
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() || !readable(tty, left) {
+                break;
+            }
+            let n = tty.read(&mut buf).ok()?;
+            if n == 0 {
+                break;
+            }
+            reply.extend_from_slice(&buf[..n]);
+            if answered_da1(&reply) {
+                break;
+            }
+        }
+        Some(reply)
+    }
+
+    fn answered_da1(reply: &[u8]) -> bool {
+        reply
+            .windows(3)
+            .position(|w| w == b"\x1b[?")
+            .is_some_and(|start| reply[start..].contains(&b'c'))
+    }
+
+    fn readable(tty: &File, timeout: Duration) -> bool {
+        let mut fd = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+        // SAFETY: `fd` is a valid pollfd for the duration of the call.
+        unsafe { libc::poll(&mut fd, 1, ms) > 0 }
+    }
+}
+
+#[cfg(not(unix))]
+mod probe {
+    /// Querying the console is not supported here; rely on the environment.
+    pub fn query(_request: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+}
@@ -0,0 +1,112 @@
+use crate::error::{Error, Result};
+use image::error::ImageFormatHint;
+use image::{DynamicImage, ImageError};
+use serde::Deserialize;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The range of stored values a DICOM image shows from black to white, given
+/// as `CENTER,WIDTH` like the window controls of radiology viewers.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Window {
+    pub center: f64,
+    pub width: f64,
+}
+
+impl FromStr for Window {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let value = |n: &str| n.trim().parse::<f64>().ok().filter(|n| n.is_finite());
+        match s.split_once(',').map(|(c, w)| (value(c), value(w))) {
+            Some((Some(center), Some(width))) if width > 0.0 => Ok(Window { center, width }),
+            Some((Some(_), Some(_))) => Err(format!("the width of `{s}` is not positive")),
+            _ => Err(format!("`{s}` is not a window such as `40,400`")),
+        }
+    }
+}
+
+impl TryFrom<String> for Window {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Offset of the `DICM` magic, after the preamble of DICOM files.
+const PREAMBLE: usize = 128;
+
+/// Whether the data is a DICOM file, with or without its preamble.
+pub fn is_dicom(head: &[u8]) -> bool {
+    head.starts_with(b"DICM") || head.get(PREAMBLE..PREAMBLE + 4) == Some(b"DICM")
+}
+
+/// Decodes frame `frame`, counted from 1, of a DICOM file, with grayscale
+/// values mapped through `window` or else the window the file itself
+/// suggests.
+#[cfg(feature = "dicom")]
+pub fn decode(
+    data: &[u8],
+    frame: usize,
+    window: Option<Window>,
+    input: &Path,
+) -> Result<DynamicImage> {
+    use dicom_object::OpenFileOptions;
+    use dicom_object::file::ReadPreamble;
+    use dicom_pixeldata::{ConvertOptions, PixelDecoder, VoiLutOption, WindowLevel};
+    use image::error::DecodingError;
+
+    let fail = |reason: String| {
+        let hint = ImageFormatHint::Name("DICOM".to_string());
+        Error::image(
+            input,
+            ImageError::Decoding(DecodingError::new(hint, reason)),
+        )
+    };
+    let data = if data.starts_with(b"DICM") {
+        data
+    } else {
+        &data[PREAMBLE..]
+    };
+    let object = OpenFileOptions::new()
+        .read_preamble(ReadPreamble::Never)
+        .from_reader(data)
+        .map_err(|e| fail(e.to_string()))?;
+    let pixels = object
+        .decode_pixel_data()
+        .map_err(|e| fail(e.to_string()))?;
+    let frames = pixels.number_of_frames();
+    let index = frame
+        .checked_sub(1)
+        .and_then(|i| u32::try_from(i).ok())
+        .filter(|&i| i < frames)
+        .ok_or_else(|| fail(format!("there is no frame {frame}, the file has {frames}")))?;
+    let voi_lut = match window {
+        Some(Window { center, width }) => {
+            log::info!("windowing at {center} around {width}");
+            VoiLutOption::Custom(WindowLevel { center, width })
+        }
+        None => VoiLutOption::Default,
+    };
+    log::info!(
+        "decoding frame {frame} of {frames} of {}x{} DICOM image",
+        pixels.columns(),
+        pixels.rows()
+    );
+    pixels
+        .to_dynamic_image_with_options(index, &ConvertOptions::new().with_voi_lut(voi_lut))
+        .map_err(|e| fail(e.to_string()))
+}
+
+#[cfg(not(feature = "dicom"))]
+pub fn decode(_: &[u8], _: usize, _: Option<Window>, input: &Path) -> Result<DynamicImage> {
+    use image::error::{UnsupportedError, UnsupportedErrorKind};
+
+    log::warn!("climg was built without the `dicom` feature");
+    let hint = ImageFormatHint::Name("DICOM".to_string());
+    let e =
+        UnsupportedError::from_format_and_kind(hint.clone(), UnsupportedErrorKind::Format(hint));
+    Err(Error::image(input, ImageError::Unsupported(e)))
+}
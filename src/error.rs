@@ -0,0 +1,108 @@
+use image::ImageError;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Everything that can go wrong while turning an image into terminal output.
+/// Each variant maps to its own exit code so scripts can tell them apart.
+#[derive(Debug)]
+pub enum Error {
+    /// The config file could not be read or parsed, or names an unknown preset.
+    Config(String),
+    /// The input file does not exist.
+    NotFound(PathBuf),
+    /// Reading the input or writing the output file failed.
+    Io(PathBuf, io::Error),
+    /// The input is not in a format climg can decode.
+    UnsupportedFormat(PathBuf, ImageError),
+    /// The input is in a known format but its data could not be decoded.
+    Decode(PathBuf, ImageError),
+    /// Writing to the terminal failed.
+    Terminal(io::Error),
+    /// The input is a URL that could not be downloaded.
+    Download(PathBuf, String),
+    /// Ctrl-C stopped an animation.
+    Interrupted,
+    /// Some of several inputs failed. Each failure has already been
+    /// reported; `first` decides the exit code.
+    Inputs {
+        failed: usize,
+        total: usize,
+        first: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Classifies an error that occurred while opening `path`.
+    pub fn open(path: &Path, e: io::Error) -> Self {
+        if e.kind() == io::ErrorKind::NotFound {
+            Error::NotFound(path.to_path_buf())
+        } else {
+            Error::Io(path.to_path_buf(), e)
+        }
+    }
+
+    /// Classifies an error reported by the `image` crate while reading `path`.
+    pub fn image(path: &Path, e: ImageError) -> Self {
+        match e {
+            ImageError::IoError(e) => Error::open(path, e),
+            ImageError::Unsupported(_) => Error::UnsupportedFormat(path.to_path_buf(), e),
+            e => Error::Decode(path.to_path_buf(), e),
+        }
+    }
+
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            Error::Config(_) => 3,
+            Error::NotFound(_) => 4,
+            Error::Io(..) => 5,
+            Error::UnsupportedFormat(..) => 6,
+            Error::Decode(..) => 7,
+            Error::Terminal(_) => 8,
+            Error::Download(..) => 9,
+            Error::Interrupted => 130,
+            Error::Inputs { first, .. } => return first.exit_code(),
+        })
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config(msg) => write!(f, "{msg}"),
+            Error::NotFound(path) => write!(f, "{}: no such file", path.display()),
+            Error::Io(path, e) => write!(f, "{}: {e}", path.display()),
+            Error::UnsupportedFormat(path, e) => write!(f, "{}: {e}", path.display()),
+            Error::Decode(path, e) => write!(f, "{}: could not decode image: {e}", path.display()),
+            Error::Terminal(e) => write!(f, "could not write to the terminal: {e}"),
+            Error::Download(url, reason) => {
+                write!(f, "{}: could not download: {reason}", url.display())
+            }
+            Error::Interrupted => write!(f, "interrupted"),
+            Error::Inputs { failed, total, .. } => write!(f, "{failed} of {total} inputs failed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Config(_) | Error::NotFound(_) | Error::Download(..) | Error::Interrupted => {
+                None
+            }
+            Error::Io(_, e) | Error::Terminal(e) => Some(e),
+            Error::UnsupportedFormat(_, e) | Error::Decode(_, e) => Some(e),
+            Error::Inputs { first, .. } => Some(first.as_ref()),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    /// Bare I/O errors only come from writing the output to stdout.
+    fn from(e: io::Error) -> Self {
+        Error::Terminal(e)
+    }
+}
@@ -0,0 +1,249 @@
+use crate::options::RenderOptions;
+use clap::ValueEnum;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use serde::Deserialize;
+use std::num::NonZeroU16;
+
+mod seam;
+
+/// How the image is scaled into the available character grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FitMode {
+    /// Fit the whole image inside the grid, keeping the aspect ratio
+    Contain,
+    /// Fill the whole grid, keeping the aspect ratio and cropping the overflow
+    Cover,
+    /// Fill the whole grid, ignoring the aspect ratio
+    Stretch,
+    /// Fit the width and let the height follow (may scroll)
+    Width,
+    /// Fit the height and let the width follow
+    Height,
+}
+
+/// How the image is brought to a different aspect ratio by --fit stretch and
+/// cover.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Resize {
+    /// Scale the image as a whole, distorting or cropping it
+    #[default]
+    Scale,
+    /// Remove the seams of pixels with the least detail first (up to a third
+    /// of the width or height), which keeps the subject intact
+    Seam,
+}
+
+/// How pixels are interpolated when the image is scaled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Filter {
+    /// The nearest pixel, keeps the hard edges of pixel art
+    Nearest,
+    /// Linear interpolation
+    Triangle,
+    /// Cubic interpolation, sharp with little ringing
+    #[value(name = "catmullrom")]
+    #[serde(rename = "catmullrom")]
+    CatmullRom,
+    /// A Gaussian, smooth but soft
+    Gaussian,
+    /// The sharpest, but rings around hard edges such as text
+    #[default]
+    Lanczos3,
+}
+
+impl From<Filter> for FilterType {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::Nearest => FilterType::Nearest,
+            Filter::Triangle => FilterType::Triangle,
+            Filter::CatmullRom => FilterType::CatmullRom,
+            Filter::Gaussian => FilterType::Gaussian,
+            Filter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+fn get_terminal_size() -> std::result::Result<(u16, u16), std::io::Error> {
+    use crossterm::terminal::size;
+    let (cols, rows) = size()?;
+    Ok((cols, rows))
+}
+
+/// Size in pixels of one terminal character cell, for the modes that draw
+/// real pixels. Falls back to 10x20 when the terminal does not report it.
+pub fn cell_pixels() -> (u32, u32) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => {
+            let cell = (
+                (size.width / size.columns).max(1) as u32,
+                (size.height / size.rows).max(1) as u32,
+            );
+            log::debug!("cell size={}x{}px", cell.0, cell.1);
+            cell
+        }
+        _ => {
+            log::debug!("terminal did not report its pixel size, assuming 10x20px cells");
+            (10, 20)
+        }
+    }
+}
+
+/// Resizes the image for the requested character grid, or the terminal when
+/// no size was given. Every character cell stands for `cell` (width, height)
+/// pixels, e.g. 2x4 dots for braille.
+pub fn fit_image(img: &DynamicImage, opts: &RenderOptions, cell: (u32, u32)) -> DynamicImage {
+    let filter = FilterType::from(opts.filter);
+    let mode = fit_mode(opts);
+    let (cols, rows) = grid(opts);
+    let target_width = cols as u32 * cell.0;
+    let target_height = rows as u32 * cell.1;
+
+    // Scaling would blur a bilevel image, so braille keeps it at its native
+    // size, one pixel per dot, unless a size was asked for or it does not fit.
+    if opts.bilevel
+        && opts.mode.is_braille()
+        && opts.width.is_none()
+        && opts.height.is_none()
+        && opts.fit.is_none()
+        && img.width() <= target_width
+        && img.height() <= target_height
+    {
+        log::info!(
+            "keeping bilevel {}x{} at its size",
+            img.width(),
+            img.height()
+        );
+        return img.clone();
+    }
+
+    let resized = match mode {
+        FitMode::Contain => img.resize(target_width, target_height, filter),
+        FitMode::Stretch | FitMode::Cover if opts.resize == Resize::Seam => {
+            seam::resize(img, target_width, target_height, filter)
+        }
+        FitMode::Cover if opts.smart_crop => {
+            let (x, y, w, h) = smart_crop(img, target_width, target_height);
+            img.crop_imm(x, y, w, h)
+                .resize_exact(target_width, target_height, filter)
+        }
+        FitMode::Cover => img.resize_to_fill(target_width, target_height, filter),
+        FitMode::Stretch => img.resize_exact(target_width, target_height, filter),
+        FitMode::Width => img.resize(target_width, u32::MAX, filter),
+        FitMode::Height => img.resize(u32::MAX, target_height, filter),
+    };
+    log::info!(
+        "resized {}x{} -> {}x{} fit={mode:?} grid={cols}x{rows}",
+        img.width(),
+        img.height(),
+        resized.width(),
+        resized.height()
+    );
+    resized
+}
+
+/// The scaling mode, derived from which sizes were given unless set.
+fn fit_mode(opts: &RenderOptions) -> FitMode {
+    opts.fit.unwrap_or(match (opts.width, opts.height) {
+        (Some(_), Some(_)) => FitMode::Stretch,
+        (Some(_), None) => FitMode::Width,
+        (None, Some(_)) => FitMode::Height,
+        (None, None) => FitMode::Contain,
+    })
+}
+
+/// The output grid in character cells; sizes that were not given come from
+/// the terminal, leaving two rows for the prompt.
+fn grid(opts: &RenderOptions) -> (u16, u16) {
+    match (opts.width, opts.height) {
+        (Some(cols), Some(rows)) => (cols.get(), rows.get()),
+        (width, height) => {
+            let (cols, rows) = match get_terminal_size() {
+                Ok(size) => {
+                    log::debug!("terminal size={}x{}", size.0, size.1);
+                    size
+                }
+                Err(e) => {
+                    log::warn!("could not detect the terminal size, assuming 100x200: {e}");
+                    (100, 200)
+                }
+            };
+            (
+                width.map_or(cols, NonZeroU16::get),
+                height.map_or(rows.saturating_sub(2), NonZeroU16::get),
+            )
+        }
+    }
+}
+
+/// The factor an image of `width`x`height` pixels is scaled by to reach the
+/// size `fit_image` gives it, for sources such as vector graphics that can
+/// be drawn at any size.
+pub fn scale(width: f32, height: f32, opts: &RenderOptions, cell: (u32, u32)) -> f32 {
+    let (cols, rows) = grid(opts);
+    let x = (cols as u32 * cell.0) as f32 / width;
+    let y = (rows as u32 * cell.1) as f32 / height;
+    match fit_mode(opts) {
+        FitMode::Contain => x.min(y),
+        FitMode::Cover | FitMode::Stretch => x.max(y),
+        FitMode::Width => x,
+        FitMode::Height => y,
+    }
+}
+
+/// Side of the downscaled copy the crop window of --smart-crop is picked on.
+const SMART_CROP_SIZE: u32 = 256;
+
+/// The region of `img` with the aspect ratio of `width`x`height` that has the
+/// most edges, as `(x, y, width, height)`. Only one axis is ever cut, so it
+/// slides a window along that axis over the column or row sums of the
+/// gradient magnitude; ties go to the window nearest the center.
+fn smart_crop(img: &DynamicImage, width: u32, height: u32) -> (u32, u32, u32, u32) {
+    let (iw, ih) = img.dimensions();
+    let scale = (width as f64 / iw as f64).max(height as f64 / ih as f64);
+    let cw = ((width as f64 / scale).round() as u32).clamp(1, iw);
+    let ch = ((height as f64 / scale).round() as u32).clamp(1, ih);
+    if cw == iw && ch == ih {
+        return (0, 0, iw, ih);
+    }
+
+    let small = img
+        .resize(SMART_CROP_SIZE, SMART_CROP_SIZE, FilterType::Triangle)
+        .to_luma8();
+    let (sw, sh) = small.dimensions();
+    let horizontal = cw < iw;
+    let mut profile = vec![0u64; if horizontal { sw } else { sh } as usize];
+    for y in 1..sh.saturating_sub(1) {
+        for x in 1..sw.saturating_sub(1) {
+            let at = |x: u32, y: u32| small.get_pixel(x, y)[0] as i32;
+            let gx = at(x + 1, y) - at(x - 1, y);
+            let gy = at(x, y + 1) - at(x, y - 1);
+            let i = if horizontal { x } else { y };
+            profile[i as usize] += (gx.unsigned_abs() + gy.unsigned_abs()) as u64;
+        }
+    }
+
+    let (full, kept) = if horizontal { (iw, cw) } else { (ih, ch) };
+    let ratio = profile.len() as f64 / full as f64;
+    let window = ((kept as f64 * ratio).round() as usize).clamp(1, profile.len());
+    let center = (profile.len() - window) as f64 / 2.0;
+    let mut sum: u64 = profile[..window].iter().sum();
+    let mut best = (sum, 0);
+    for start in 1..=profile.len() - window {
+        sum = sum + profile[start + window - 1] - profile[start - 1];
+        let nearer = (start as f64 - center).abs() < (best.1 as f64 - center).abs();
+        if sum > best.0 || (sum == best.0 && nearer) {
+            best = (sum, start);
+        }
+    }
+    let offset = ((best.1 as f64 / ratio).round() as u32).min(full - kept);
+    log::debug!("smart crop offset={offset} of {}", full - kept);
+    if horizontal {
+        (offset, 0, cw, ih)
+    } else {
+        (0, offset, iw, ch)
+    }
+}
@@ -0,0 +1,100 @@
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GenericImageView, RgbaImage};
+
+/// Share of the scaled image's width or height that seams are removed from
+/// at most; the rest of the aspect change is squeezed as usual.
+const MAX_CARVE: f64 = 1.0 / 3.0;
+
+/// Resizes the image to exactly `width`x`height`: it is scaled until it
+/// covers that size, then the overflowing axis is narrowed by removing the
+/// seams with the least detail, and whatever is left beyond [`MAX_CARVE`] is
+/// squeezed.
+pub fn resize(img: &DynamicImage, width: u32, height: u32, filter: FilterType) -> DynamicImage {
+    let (iw, ih) = img.dimensions();
+    let scale = (width as f64 / iw as f64).max(height as f64 / ih as f64);
+    let sw = ((iw as f64 * scale).round() as u32).max(width);
+    let sh = ((ih as f64 * scale).round() as u32).max(height);
+    let mut rgba = img.resize_exact(sw, sh, filter).to_rgba8();
+    if sw > width {
+        let keep = width.max(sw - (sw as f64 * MAX_CARVE) as u32);
+        rgba = carve(&rgba, keep);
+    } else if sh > height {
+        let keep = height.max(sh - (sh as f64 * MAX_CARVE) as u32);
+        rgba = imageops::rotate270(&carve(&imageops::rotate90(&rgba), keep));
+    }
+    log::debug!(
+        "seam carved {sw}x{sh} -> {}x{}",
+        rgba.width(),
+        rgba.height()
+    );
+    let img = DynamicImage::ImageRgba8(rgba);
+    if img.dimensions() == (width, height) {
+        img
+    } else {
+        img.resize_exact(width, height, filter)
+    }
+}
+
+/// Removes vertical seams, 8-connected paths from the top to the bottom row
+/// through the pixels with the smallest gradient, until the image is `width`
+/// pixels wide.
+fn carve(img: &RgbaImage, width: u32) -> RgbaImage {
+    let h = img.height() as usize;
+    let mut w = img.width() as usize;
+    let mut pixels: Vec<[u8; 4]> = img.pixels().map(|p| p.0).collect();
+    let mut luma: Vec<i32> = pixels
+        .iter()
+        .map(|[r, g, b, a]| {
+            let v = (2126 * *r as i32 + 7152 * *g as i32 + 722 * *b as i32) / 10000;
+            v * *a as i32 / 255
+        })
+        .collect();
+    let mut cost = vec![0u32; w * h];
+    while w > width as usize {
+        // Cheapest path from the top row to every pixel.
+        for y in 0..h {
+            for x in 0..w {
+                let at = |x: usize, y: usize| luma[y * w + x];
+                let gx = at((x + 1).min(w - 1), y) - at(x.saturating_sub(1), y);
+                let gy = at(x, (y + 1).min(h - 1)) - at(x, y.saturating_sub(1));
+                let energy = gx.unsigned_abs() + gy.unsigned_abs();
+                cost[y * w + x] = energy
+                    + if y == 0 {
+                        0
+                    } else {
+                        let above = &cost[(y - 1) * w..y * w];
+                        above[x.saturating_sub(1)..(x + 2).min(w)]
+                            .iter()
+                            .copied()
+                            .min()
+                            .unwrap_or(0)
+                    };
+            }
+        }
+        // Follow the cheapest path back up and drop it.
+        let last = &cost[(h - 1) * w..h * w];
+        let mut seam = vec![(0..w).min_by_key(|&x| last[x]).unwrap_or(0); h];
+        for y in (0..h - 1).rev() {
+            let (x, row) = (seam[y + 1], &cost[y * w..(y + 1) * w]);
+            seam[y] = (x.saturating_sub(1)..(x + 2).min(w))
+                .min_by_key(|&x| row[x])
+                .unwrap_or(x);
+        }
+        remove_seam(&mut pixels, w, &seam);
+        remove_seam(&mut luma, w, &seam);
+        w -= 1;
+    }
+    RgbaImage::from_fn(w as u32, h as u32, |x, y| {
+        pixels[y as usize * w + x as usize].into()
+    })
+}
+
+/// Drops the pixel at `seam[y]` from every row of `w` pixels.
+fn remove_seam<T>(values: &mut Vec<T>, w: usize, seam: &[usize]) {
+    let mut i = 0;
+    values.retain(|_| {
+        let (x, y) = (i % w, i / w);
+        i += 1;
+        x != seam[y]
+    });
+}
@@ -0,0 +1,166 @@
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Expands the wildcard patterns among `inputs` so that `climg "photos/*.jpg"`
+/// works even where the shell leaves globs alone (e.g. on Windows). `*`, `?`
+/// and `[...]` are supported in every path component. The matches of each
+/// pattern are sorted naturally, so `img2` comes before `img10`. A pattern
+/// that matches nothing is kept as is and fails later as a missing file.
+pub fn expand(inputs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut expanded = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        if input.exists() || !input.to_str().is_some_and(is_pattern) {
+            expanded.push(input.clone());
+            continue;
+        }
+
+        let mut matches = expand_pattern(input);
+        log::debug!("{} matched {} files", input.display(), matches.len());
+        if matches.is_empty() {
+            expanded.push(input.clone());
+            continue;
+        }
+        matches.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
+        expanded.extend(matches);
+    }
+    expanded
+}
+
+fn is_pattern(s: &str) -> bool {
+    s.contains(['*', '?', '['])
+}
+
+/// Walks `pattern` one component at a time, listing the directories reached
+/// so far whenever a component contains wildcards.
+fn expand_pattern(pattern: &Path) -> Vec<PathBuf> {
+    let mut candidates = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let name = match component {
+            Component::Normal(name) => name.to_str().filter(|name| is_pattern(name)),
+            _ => None,
+        };
+        let Some(name) = name else {
+            for candidate in &mut candidates {
+                candidate.push(component);
+            }
+            continue;
+        };
+
+        let glob: Vec<char> = name.chars().collect();
+        let mut next = Vec::new();
+        for dir in &candidates {
+            let listing = if dir.as_os_str().is_empty() {
+                fs::read_dir(".")
+            } else {
+                fs::read_dir(dir)
+            };
+            let Ok(listing) = listing else {
+                continue;
+            };
+            for entry in listing.flatten() {
+                let file_name = entry.file_name();
+                let Some(file_name) = file_name.to_str() else {
+                    continue;
+                };
+                // Like the shells, only match hidden files when asked to.
+                if file_name.starts_with('.') && !name.starts_with('.') {
+                    continue;
+                }
+                let chars: Vec<char> = file_name.chars().collect();
+                if matches(&glob, &chars) {
+                    next.push(dir.join(file_name));
+                }
+            }
+        }
+        candidates = next;
+    }
+    candidates.retain(|path| path.exists());
+    candidates
+}
+
+/// Matches `name` against a single component pattern.
+fn matches(glob: &[char], name: &[char]) -> bool {
+    match glob.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| matches(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && matches(rest, &name[1..]),
+        Some(('[', rest)) => match (name.first(), match_class(rest)) {
+            (Some(&c), Some((class, rest))) => class(c) && matches(rest, &name[1..]),
+            // An unterminated `[` is an ordinary character.
+            (Some(&c), None) => c == '[' && matches(rest, &name[1..]),
+            (None, _) => false,
+        },
+        Some((&c, rest)) => name.first() == Some(&c) && matches(rest, &name[1..]),
+    }
+}
+
+/// Parses the inside of a `[...]` class, e.g. `abc`, `a-z` or `!0-9`, and
+/// returns a predicate for it together with the rest of the pattern.
+fn match_class(glob: &[char]) -> Option<(impl Fn(char) -> bool, &[char])> {
+    let (negated, glob) = match glob.split_first() {
+        Some(('!' | '^', rest)) => (true, rest),
+        _ => (false, glob),
+    };
+    // A `]` right after the opening bracket is part of the class.
+    let end = glob.iter().skip(1).position(|&c| c == ']')? + 1;
+    let class = &glob[..end];
+    let predicate = move |c: char| {
+        let mut found = false;
+        let mut i = 0;
+        while i < class.len() {
+            if i + 2 < class.len() && class[i + 1] == '-' {
+                found |= (class[i]..=class[i + 2]).contains(&c);
+                i += 3;
+            } else {
+                found |= class[i] == c;
+                i += 1;
+            }
+        }
+        found != negated
+    };
+    Some((predicate, &glob[end + 1..]))
+}
+
+/// Compares two strings treating runs of digits as numbers, so that `img2`
+/// sorts before `img10`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a);
+                let y = take_number(&mut b);
+                // Compare by magnitude first, then by leading zeros.
+                let ordering = x
+                    .trim_start_matches('0')
+                    .len()
+                    .cmp(&y.trim_start_matches('0').len())
+                    .then_with(|| x.trim_start_matches('0').cmp(y.trim_start_matches('0')))
+                    .then_with(|| x.len().cmp(&y.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    let mut number = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        number.push(c);
+    }
+    number
+}
@@ -0,0 +1,82 @@
+use crate::error::{Error, Result};
+use image::error::ImageFormatHint;
+use image::{DynamicImage, ImageError};
+use std::path::Path;
+
+/// Brands of the HEIF container that hold HEIC and AVIF still images.
+const BRANDS: [&[u8]; 10] = [
+    b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx", b"mif1", b"msf1", b"avif", b"avis",
+];
+
+/// Whether the data starts with the `ftyp` box of a HEIC or AVIF file.
+pub fn is_heif(head: &[u8]) -> bool {
+    if head.len() < 16 || &head[4..8] != b"ftyp" {
+        return false;
+    }
+    let size = u32::from_be_bytes([head[0], head[1], head[2], head[3]]) as usize;
+    // The major brand, the minor version and the compatible brands.
+    head[8..size.clamp(16, head.len())]
+        .chunks_exact(4)
+        .enumerate()
+        .any(|(i, brand)| i != 1 && BRANDS.contains(&brand))
+}
+
+/// Decodes the primary image of a HEIC or AVIF file. Their orientation is
+/// part of the container rather than the EXIF data, and is applied unless
+/// `rotate` is off.
+#[cfg(feature = "heif")]
+pub fn decode(data: &[u8], input: &Path, rotate: bool) -> Result<DynamicImage> {
+    use image::error::DecodingError;
+    use image::{RgbImage, RgbaImage};
+    use libheif_rs::{ColorSpace, DecodingOptions, HeifContext, LibHeif, RgbChroma};
+
+    let fail = |e: libheif_rs::HeifError| {
+        let hint = ImageFormatHint::Name("HEIF".to_string());
+        Error::image(input, ImageError::Decoding(DecodingError::new(hint, e)))
+    };
+    let context = HeifContext::read_from_bytes(data).map_err(fail)?;
+    let handle = context.primary_image_handle().map_err(fail)?;
+    let alpha = handle.has_alpha_channel();
+    let chroma = if alpha {
+        RgbChroma::Rgba
+    } else {
+        RgbChroma::Rgb
+    };
+    let mut options = DecodingOptions::new();
+    if let Some(options) = &mut options {
+        options.set_ignore_transformations(!rotate);
+        options.set_convert_hdr_to_8bit(true);
+    }
+    let image = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(chroma), options)
+        .map_err(fail)?;
+    let plane = image
+        .planes()
+        .interleaved
+        .expect("RGB images are interleaved");
+    let row = plane.width as usize * if alpha { 4 } else { 3 };
+    let pixels: Vec<u8> = plane
+        .data
+        .chunks(plane.stride)
+        .flat_map(|line| &line[..row])
+        .copied()
+        .collect();
+    let (w, h) = (plane.width, plane.height);
+    let img = if alpha {
+        RgbaImage::from_raw(w, h, pixels).map(DynamicImage::ImageRgba8)
+    } else {
+        RgbImage::from_raw(w, h, pixels).map(DynamicImage::ImageRgb8)
+    };
+    Ok(img.expect("one row per line of the plane"))
+}
+
+#[cfg(not(feature = "heif"))]
+pub fn decode(_: &[u8], input: &Path, _: bool) -> Result<DynamicImage> {
+    use image::error::{UnsupportedError, UnsupportedErrorKind};
+
+    log::warn!("climg was built without the `heif` feature");
+    let hint = ImageFormatHint::Name("HEIF".to_string());
+    let e =
+        UnsupportedError::from_format_and_kind(hint.clone(), UnsupportedErrorKind::Format(hint));
+    Err(Error::image(input, ImageError::Unsupported(e)))
+}
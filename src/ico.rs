@@ -0,0 +1,71 @@
+use crate::error::{Error, Result};
+use image::{DynamicImage, ImageFormat};
+use std::cmp::Reverse;
+use std::path::Path;
+
+/// One image of an ICO file, as listed in its directory.
+struct Entry<'a> {
+    side: u32,
+    bpp: u16,
+    /// The 16 bytes of the directory entry.
+    header: &'a [u8],
+    data: &'a [u8],
+}
+
+/// The images an ICO file lists, `None` when its directory is cut short or
+/// points outside the file.
+fn entries(data: &[u8]) -> Option<Vec<Entry<'_>>> {
+    let count = u16::from_le_bytes([*data.get(4)?, *data.get(5)?]) as usize;
+    (0..count)
+        .map(|i| {
+            let header = data.get(6 + i * 16..6 + (i + 1) * 16)?;
+            // 0 stands for 256 pixels.
+            let side = |b: u8| if b == 0 { 256 } else { u32::from(b) };
+            let word = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+            let (size, offset) = (word(8) as usize, word(12) as usize);
+            Some(Entry {
+                side: side(header[0]).max(side(header[1])),
+                bpp: u16::from_le_bytes([header[6], header[7]]),
+                header,
+                data: data.get(offset..offset.checked_add(size)?)?,
+            })
+        })
+        .collect()
+}
+
+/// Decodes one image of an ICO file: the smallest one that is at least `size`
+/// pixels wide or high, or the largest one when none is or no size is asked
+/// for. The deepest colors win between images of the same size.
+pub fn decode(data: &[u8], input: &Path, size: Option<u32>) -> Result<DynamicImage> {
+    let decode = |data: &[u8]| {
+        image::load_from_memory_with_format(data, ImageFormat::Ico)
+            .map_err(|e| Error::image(input, e))
+    };
+    // A damaged directory is left for the decoder to report.
+    let Some(entries) = entries(data).filter(|entries| !entries.is_empty()) else {
+        return decode(data);
+    };
+    let largest = || entries.iter().max_by_key(|e| (e.side, e.bpp));
+    let entry = match size {
+        Some(size) => entries
+            .iter()
+            .filter(|e| e.side >= size)
+            .min_by_key(|e| (e.side, Reverse(e.bpp)))
+            .or_else(largest),
+        None => largest(),
+    }
+    .expect("at least one entry");
+    log::info!(
+        "picking the {0}x{0} {1}-bit image of {2} in the icon",
+        entry.side,
+        entry.bpp,
+        entries.len()
+    );
+
+    // An ICO file holding only the picked image, right after its directory.
+    let mut single = vec![0, 0, 1, 0, 1, 0];
+    single.extend_from_slice(&entry.header[..12]);
+    single.extend_from_slice(&22u32.to_le_bytes());
+    single.extend_from_slice(entry.data);
+    decode(&single)
+}
@@ -0,0 +1,273 @@
+use crate::base64;
+use crate::error::{Error, Result};
+use image::{DynamicImage, GrayImage, ImageReader, RgbImage, RgbaImage};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::str::FromStr;
+
+/// The bytes of an input: a file, or data that was read into memory because
+/// it cannot be seeked, such as the standard input.
+pub enum Source {
+    File(BufReader<File>),
+    Memory(Cursor<Vec<u8>>),
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Source::File(r) => r.read(buf),
+            Source::Memory(r) => r.read(buf),
+        }
+    }
+}
+
+impl BufRead for Source {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match self {
+            Source::File(r) => r.fill_buf(),
+            Source::Memory(r) => r.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amount: usize) {
+        match self {
+            Source::File(r) => r.consume(amount),
+            Source::Memory(r) => r.consume(amount),
+        }
+    }
+}
+
+impl Seek for Source {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Source::File(r) => r.seek(pos),
+            Source::Memory(r) => r.seek(pos),
+        }
+    }
+}
+
+/// Whether the input is `-`, which stands for the standard input.
+pub fn is_stdin(input: &Path) -> bool {
+    input.as_os_str() == "-"
+}
+
+/// Whether the input is an `http://` or `https://` URL.
+pub fn is_url(input: &Path) -> bool {
+    input
+        .to_str()
+        .is_some_and(|s| s.starts_with("http://") || s.starts_with("https://"))
+}
+
+/// Whether the input is a `data:` URI carrying the image itself.
+pub fn is_data_uri(input: &Path) -> bool {
+    input.to_str().is_some_and(|s| s.starts_with("data:"))
+}
+
+/// Whether the input names a file, rather than the standard input, a URL or
+/// a data URI.
+pub fn is_file(input: &Path) -> bool {
+    !is_stdin(input) && !is_url(input) && !is_data_uri(input)
+}
+
+/// The data of a `data:[<media type>][;base64],<data>` URI, which is
+/// percent-encoded unless it is base64.
+fn data_uri(input: &Path) -> Result<Vec<u8>> {
+    let invalid = |reason: &str| {
+        let e = io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid data URI: {reason}"),
+        );
+        Error::Io(input.to_path_buf(), e)
+    };
+    let uri = input.to_str().unwrap_or_default();
+    let (header, data) = uri["data:".len()..]
+        .split_once(',')
+        .ok_or_else(|| invalid("no comma before the data"))?;
+    if header.ends_with(";base64") {
+        return base64::decode(data).ok_or_else(|| invalid("bad base64"));
+    }
+    let mut bytes = Vec::with_capacity(data.len());
+    let mut rest = data.as_bytes();
+    while let [c, tail @ ..] = rest {
+        rest = tail;
+        if *c != b'%' {
+            bytes.push(*c);
+            continue;
+        }
+        let hex = tail.get(..2).and_then(|h| std::str::from_utf8(h).ok());
+        let byte = hex.and_then(|h| u8::from_str_radix(h, 16).ok());
+        bytes.push(byte.ok_or_else(|| invalid("bad percent escape"))?);
+        rest = &tail[2..];
+    }
+    Ok(bytes)
+}
+
+/// Largest download accepted from a URL.
+#[cfg(feature = "http")]
+const MAX_DOWNLOAD: u64 = 64 << 20;
+
+/// How long a download may take altogether.
+#[cfg(feature = "http")]
+const DOWNLOAD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Downloads the image at `url`.
+#[cfg(feature = "http")]
+fn download(url: &Path) -> Result<Vec<u8>> {
+    let fail = |e: ureq::Error| Error::Download(url.to_path_buf(), e.to_string());
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(DOWNLOAD_TIMEOUT))
+        .build()
+        .into();
+    let mut response = agent
+        .get(url.to_string_lossy().as_ref())
+        .call()
+        .map_err(fail)?;
+    let bytes = response
+        .body_mut()
+        .with_config()
+        .limit(MAX_DOWNLOAD)
+        .read_to_vec()
+        .map_err(fail)?;
+    log::debug!("downloaded {} bytes from {}", bytes.len(), url.display());
+    Ok(bytes)
+}
+
+#[cfg(not(feature = "http"))]
+fn download(url: &Path) -> Result<Vec<u8>> {
+    Err(Error::Download(
+        url.to_path_buf(),
+        "climg was built without the `http` feature".to_string(),
+    ))
+}
+
+/// Opens the input, with its format guessed from its first bytes rather
+/// than its extension. `-` reads the whole standard input, URLs are
+/// downloaded first and data URIs decoded.
+pub fn open(input: &Path) -> Result<ImageReader<Source>> {
+    let source = if is_data_uri(input) {
+        Source::Memory(Cursor::new(data_uri(input)?))
+    } else if is_url(input) {
+        Source::Memory(Cursor::new(download(input)?))
+    } else if is_stdin(input) {
+        let mut bytes = Vec::new();
+        io::stdin()
+            .lock()
+            .read_to_end(&mut bytes)
+            .map_err(|e| Error::Io(input.to_path_buf(), e))?;
+        log::debug!("read {} bytes from stdin", bytes.len());
+        Source::Memory(Cursor::new(bytes))
+    } else {
+        Source::File(BufReader::new(
+            File::open(input).map_err(|e| Error::open(input, e))?,
+        ))
+    };
+    ImageReader::new(source)
+        .with_guessed_format()
+        .map_err(|e| Error::open(input, e))
+}
+
+/// How the bytes of a raw pixel stream are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RawPixels {
+    /// Red, green and blue, one byte each (ffmpeg's `rgb24`)
+    Rgb,
+    /// Red, green, blue and alpha, one byte each (ffmpeg's `rgba`)
+    Rgba,
+    /// One byte of brightness (ffmpeg's `gray`)
+    Gray,
+}
+
+/// The geometry --raw reads the input with, given as `WxH[:FORMAT]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Raw {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: RawPixels,
+}
+
+impl FromStr for Raw {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (size, pixels) = s.split_once(':').unwrap_or((s, "rgb"));
+        let pixels = match pixels.to_ascii_lowercase().as_str() {
+            "rgb" | "rgb24" => RawPixels::Rgb,
+            "rgba" => RawPixels::Rgba,
+            "gray" | "gray8" => RawPixels::Gray,
+            _ => return Err(format!("`{pixels}` is not one of rgb, rgba or gray")),
+        };
+        let (width, height) = size
+            .split_once(['x', 'X'])
+            .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)))
+            .filter(|&(w, h): &(u32, u32)| w > 0 && h > 0)
+            .ok_or_else(|| format!("`{size}` is not a size such as `640x480`"))?;
+        Ok(Raw {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+impl Raw {
+    /// Size in bytes of one frame.
+    fn frame_len(&self) -> usize {
+        let channels = match self.pixels {
+            RawPixels::Rgb => 3,
+            RawPixels::Rgba => 4,
+            RawPixels::Gray => 1,
+        };
+        self.width as usize * self.height as usize * channels
+    }
+
+    /// Opens the input for reading raw frames from.
+    pub fn open(&self, input: &Path) -> Result<Box<dyn Read>> {
+        if is_stdin(input) {
+            return Ok(Box::new(io::stdin().lock()));
+        }
+        let file = File::open(input).map_err(|e| Error::open(input, e))?;
+        Ok(Box::new(BufReader::new(file)))
+    }
+
+    /// Reads the next frame, `None` once the input is used up. A frame cut
+    /// short is an error.
+    pub fn read_frame(&self, reader: &mut dyn Read, input: &Path) -> Result<Option<DynamicImage>> {
+        let mut bytes = vec![0; self.frame_len()];
+        let mut filled = 0;
+        while filled < bytes.len() {
+            match reader.read(&mut bytes[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(Error::Io(input.to_path_buf(), e)),
+            }
+        }
+        if filled == 0 {
+            return Ok(None);
+        }
+        if filled < bytes.len() {
+            let e = io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("frame cut short at {filled} of {} bytes", bytes.len()),
+            );
+            return Err(Error::Io(input.to_path_buf(), e));
+        }
+        let (w, h) = (self.width, self.height);
+        let img = match self.pixels {
+            RawPixels::Rgb => RgbImage::from_raw(w, h, bytes).map(DynamicImage::ImageRgb8),
+            RawPixels::Rgba => RgbaImage::from_raw(w, h, bytes).map(DynamicImage::ImageRgba8),
+            RawPixels::Gray => GrayImage::from_raw(w, h, bytes).map(DynamicImage::ImageLuma8),
+        };
+        Ok(Some(img.expect("the buffer holds exactly one frame")))
+    }
+
+    /// Reads the first frame of the input.
+    pub fn load(&self, input: &Path) -> Result<DynamicImage> {
+        self.read_frame(&mut self.open(input)?, input)?
+            .ok_or_else(|| {
+                let e = io::Error::new(io::ErrorKind::UnexpectedEof, "no pixels to read");
+                Error::Io(input.to_path_buf(), e)
+            })
+    }
+}
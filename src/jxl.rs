@@ -0,0 +1,16 @@
+/// Signatures of a bare JPEG XL codestream and of the container around one.
+const SIGNATURES: [&[u8]; 2] = [&[0xff, 0x0a], b"\0\0\0\x0cJXL \r\n\x87\n"];
+
+/// Whether the data starts like a JPEG XL image.
+pub fn is_jxl(head: &[u8]) -> bool {
+    SIGNATURES
+        .iter()
+        .any(|signature| head.starts_with(signature))
+}
+
+/// Teaches the image crate to recognize and decode JPEG XL, which it has no
+/// decoder of its own for.
+pub fn register() {
+    #[cfg(feature = "jxl")]
+    jxl_oxide::integration::register_image_decoding_hook();
+}
@@ -1,152 +1,465 @@
-use image::ImageReader;
-use image::Luma;
-use image::{DynamicImage, ImageBuffer};
-use std::env;
-
-fn get_terminal_size() -> std::result::Result<(u16, u16), std::io::Error> {
-    use crossterm::terminal::size;
-    let (cols, rows) = size()?;
-    Ok((cols, rows))
-}
+mod anim;
+mod base64;
+mod camera;
+mod cli;
+mod config;
+mod detect;
+mod dicom;
+mod error;
+mod fit;
+mod glob;
+mod heif;
+mod ico;
+mod input;
+mod jxl;
+mod logging;
+mod options;
+mod pdf;
+mod psd;
+mod render;
+mod svg;
+mod tiff;
+mod walk;
 
-fn to_grayscale_luma8(img: DynamicImage) -> ImageBuffer<Luma<u8>, Vec<u8>> {
-    img.to_luma8()
-}
+use clap::CommandFactory;
+use clap::error::ErrorKind;
+use cli::{
+    Cli, Command, CompletionsArgs, ConvertArgs, InfoArgs, InputArgs, PlayArgs, RenderArgs, ViewArgs,
+};
+use config::Config;
+use error::{Error, Result};
+use image::metadata::Orientation;
+use image::{DynamicImage, ExtendedColorType, ImageDecoder, ImageFormat, ImageReader};
+use options::RenderOptions;
+use render::RenderMode;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
 
-fn otsu_threshold(img: &ImageBuffer<Luma<u8>, Vec<u8>>) -> u8 {
-    let mut hist = [0u32; 256];
-    for Luma([v]) in img.pixels() {
-        hist[*v as usize] += 1;
+/// Decodes the image, turned upright according to its EXIF orientation
+/// unless that was turned off, and tells whether its source stores one bit
+/// per pixel, as PBM does. Of a PDF document, a multi-page TIFF or a
+/// multi-frame DICOM file only `page`, counted from 1, is drawn, and of an
+/// ICO file the image closest to `icon_size`.
+fn decode(
+    reader: ImageReader<input::Source>,
+    input: &Path,
+    page: usize,
+    icon_size: Option<u32>,
+    opts: &RenderOptions,
+) -> Result<(DynamicImage, bool)> {
+    let format = reader.format();
+    let mut source = reader.into_inner();
+    let head = source.fill_buf().map_err(|e| Error::open(input, e))?;
+    let bigtiff = format.is_none() && tiff::is_bigtiff(head);
+    let format = if bigtiff {
+        Some(ImageFormat::Tiff)
+    } else {
+        format
+    };
+    let heif = heif::is_heif(head);
+    let svg = format.is_none() && svg::is_svg(head);
+    let pdf = format.is_none() && pdf::is_pdf(head);
+    let psd = format.is_none() && psd::is_psd(head);
+    let dicom = format.is_none() && dicom::is_dicom(head);
+    let ico = format == Some(ImageFormat::Ico);
+    // JPEG XL turns images upright while decoding, whatever their EXIF data
+    // says.
+    let upright = format.is_none() && jxl::is_jxl(head);
+    if heif || svg || pdf || psd || dicom || ico {
+        let mut data = Vec::new();
+        source
+            .read_to_end(&mut data)
+            .map_err(|e| Error::open(input, e))?;
+        let cell = opts.mode.cell_size();
+        let scale = |w, h| fit::scale(w, h, opts, cell);
+        let img = if heif {
+            heif::decode(&data, input, opts.exif_rotate)
+        } else if ico {
+            ico::decode(&data, input, icon_size)
+        } else if psd {
+            psd::decode(&data, input)
+        } else if dicom {
+            dicom::decode(&data, page, opts.dicom_window, input)
+        } else if pdf {
+            pdf::rasterize(data, page, input, scale)
+        } else {
+            svg::rasterize(&data, input, scale)
+        };
+        return img.map(|img| (img, false));
     }
-
-    let total: u32 = img.width() * img.height();
-    if total == 0 {
-        return 128;
+    if camera::maybe_raw(format)
+        && let Some(img) = camera::decode(&mut source, input, opts.exif_rotate)?
+    {
+        return Ok((img, false));
     }
-
-    let mut sum_total: f64 = 0.0;
-    for (i, &h) in hist.iter().enumerate() {
-        sum_total += (i as f64) * (h as f64);
+    if format == Some(ImageFormat::Tiff) && page > 1 {
+        let mut data = Vec::new();
+        source
+            .read_to_end(&mut data)
+            .map_err(|e| Error::open(input, e))?;
+        tiff::seek_page(&mut data, page, input)?;
+        source = input::Source::Memory(io::Cursor::new(data));
     }
+    // Formats the image crate only knows through hooks are guessed again,
+    // BigTIFF it does not guess at all.
+    let reader = if bigtiff {
+        ImageReader::with_format(source, ImageFormat::Tiff)
+    } else {
+        ImageReader::new(source)
+            .with_guessed_format()
+            .map_err(|e| Error::open(input, e))?
+    };
+    let mut decoder = reader.into_decoder().map_err(|e| Error::image(input, e))?;
+    let orientation = if upright {
+        Orientation::NoTransforms
+    } else {
+        decoder.orientation().unwrap_or(Orientation::NoTransforms)
+    };
+    let bilevel = decoder.original_color_type() == ExtendedColorType::L1;
+    let mut img = DynamicImage::from_decoder(decoder).map_err(|e| Error::image(input, e))?;
+    if opts.exif_rotate && orientation != Orientation::NoTransforms {
+        log::info!("applying EXIF orientation {orientation:?}");
+        img.apply_orientation(orientation);
+    }
+    Ok((img, bilevel))
+}
 
-    let mut sum_b: f64 = 0.0;
-    let mut w_b: f64 = 0.0;
-    let mut w_f: f64;
-    let mut max_var: f64 = -1.0;
-    let mut threshold: u8 = 0;
-
-    for (t, &h) in hist.iter().enumerate() {
-        w_b += h as f64;
-        if w_b == 0.0 {
-            continue;
-        }
-        w_f = (total as f64) - w_b;
-        if w_f == 0.0 {
-            break;
-        }
-        sum_b += (t as f64) * (h as f64);
+fn load_image(
+    reader: ImageReader<input::Source>,
+    input: &Path,
+    page: usize,
+    icon_size: Option<u32>,
+    opts: &RenderOptions,
+) -> Result<(DynamicImage, bool)> {
+    let start = Instant::now();
+    let format = reader.format();
+    let (img, bilevel) = decode(reader, input, page, icon_size, opts)?;
+    log::info!(
+        "decoded {} format={format:?} size={}x{} color={:?} took={:.1?}",
+        input.display(),
+        img.width(),
+        img.height(),
+        img.color(),
+        start.elapsed()
+    );
+    Ok((img, bilevel))
+}
 
-        let m_b = sum_b / w_b;
-        let m_f = (sum_total - sum_b) / w_f;
+/// Where the rendered output goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    /// Standard output, when it is a terminal.
+    Terminal,
+    /// Standard output piped or redirected somewhere else.
+    Pipe,
+    /// Files written by `convert`.
+    File,
+}
 
-        let var_between = w_b * w_f * (m_b - m_f) * (m_b - m_f);
-        if var_between > max_var {
-            max_var = var_between;
-            threshold = t as u8;
+/// Resolves the final render options from the config file, the selected
+/// preset and the command line, in that order of precedence. `--mode auto`
+/// asks the terminal what it supports when the output goes to one and falls
+/// back to braille otherwise.
+fn render_options(cli: &Cli, args: &RenderArgs, output: Output) -> Result<RenderOptions> {
+    let to_terminal = output == Output::Terminal;
+    let config = Config::load(cli.config.as_deref())?;
+    let args = config.resolve(cli.preset.as_deref(), args)?;
+    let mut opts = RenderOptions::from(&args);
+    if let Some(path) = &args.palette_file {
+        opts.palette = Some(render::load_palette(path)?);
+    }
+    if let Some(path) = &args.overlay {
+        let (img, _) = load_image(input::open(path)?, path, 1, None, &opts)?;
+        opts.overlay = Some(render::Overlay::new(img));
+    }
+    if opts.mode == RenderMode::Auto {
+        opts.mode = if to_terminal {
+            detect::best_mode()
+        } else {
+            RenderMode::Braille
+        };
+    }
+    if to_terminal && opts.mode.draws_pixels() {
+        match detect::tmux_passthrough() {
+            Some(true) => opts.tmux = true,
+            Some(false) => {
+                log::warn!(
+                    "tmux does not pass graphics through (set allow-passthrough on), \
+                     falling back to braille"
+                );
+                opts.mode = RenderMode::Braille;
+            }
+            None => {}
         }
     }
-
-    threshold
+    // Files are colored unless colors are turned off, like a terminal.
+    if opts.mode.uses_colors()
+        && !args
+            .color
+            .unwrap_or_default()
+            .enabled(output != Output::Pipe)
+    {
+        if args.mode.is_some_and(|mode| mode != RenderMode::Auto) {
+            log::warn!(
+                "colors are disabled, drawing braille instead of {:?}",
+                opts.mode
+            );
+        } else {
+            log::info!("colors are disabled, falling back to braille");
+        }
+        opts.mode = RenderMode::Braille;
+    }
+    if to_terminal
+        && opts.mode.monochrome()
+        && args.invert.is_none()
+        && args.auto_invert.unwrap_or(true)
+        && let Some(light) = detect::light_background()
+    {
+        log::info!(
+            "terminal background is {}",
+            if light { "light" } else { "dark" }
+        );
+        opts.invert = light;
+    }
+    log::debug!("options {opts:?}");
+    Ok(opts)
 }
 
-#[inline]
-fn bit_if_on(img: &ImageBuffer<Luma<u8>, Vec<u8>>, x: u32, y: u32, t: u8, invert: bool) -> u8 {
-    if x >= img.width() || y >= img.height() {
-        return 0;
+/// Expands the wildcard patterns among the inputs and appends the images
+/// found in the `--recursive` directories.
+fn input_paths(args: &InputArgs) -> Result<Vec<PathBuf>> {
+    let mut paths = glob::expand(&args.inputs);
+    let opts = walk::WalkOptions {
+        max_depth: args.max_depth,
+        extensions: &args.extensions,
+    };
+    for dir in &args.recursive {
+        paths.extend(walk::find_images(dir, &opts)?);
     }
-    let v = img.get_pixel(x, y)[0];
-    let on = if invert { v < t } else { v >= t };
-    if on { 1 } else { 0 }
+    Ok(paths)
 }
 
-fn fit_image(img: &DynamicImage) -> DynamicImage {
-    let image_width = img.width();
-    let image_height = img.height();
-
-    let (mut terminal_width, mut terminal_height) = get_terminal_size().unwrap_or((100, 200));
-    terminal_height -= 2;
-    terminal_height *= 4;
-    terminal_width *= 2;
-    let mut target_height = terminal_height as u32;
-    let mut target_width = terminal_width as u32;
-    let mut aspect = image_height as f32 / image_width as f32;
-
-    if aspect < 1.0 {
-        target_height = (target_width as f32 * aspect).round() as u32;
-        if target_height > terminal_height as u32 {
-            aspect = terminal_height as f32 / target_height as f32;
-            target_height = (target_height as f32 * aspect).round() as u32;
-            target_width = (target_width as f32 * aspect).round() as u32;
-        }
-    } else if aspect > 1.0 {
-        target_width = (target_height as f32 * aspect).round() as u32;
-        if target_width > terminal_width as u32 {
-            aspect = terminal_width as f32 / target_width as f32;
-            target_height = (target_height as f32 * aspect).round() as u32;
-            target_width = (target_width as f32 * aspect).round() as u32;
-        }
-    } else {
-        use std::cmp::min;
-        target_height = min(target_height, target_width);
-        target_width = min(target_height, target_width);
+/// Runs `f` on every input in turn. With several inputs a failure is
+/// reported right away and the remaining inputs are still processed; a
+/// single input fails exactly like before.
+fn for_each_input(inputs: &[PathBuf], mut f: impl FnMut(&Path) -> Result<()>) -> Result<()> {
+    if let [input] = inputs {
+        return f(input);
     }
 
-    img.resize(
-        target_width,
-        target_height,
-        image::imageops::FilterType::Lanczos3,
-    )
+    let mut failed = 0;
+    let mut first = None;
+    for input in inputs {
+        match f(input) {
+            Ok(()) => {}
+            // There is no point in going on once stdout is gone or the user
+            // asked to stop.
+            Err(e @ (Error::Terminal(_) | Error::Interrupted)) => return Err(e),
+            Err(e) => {
+                eprintln!("climg: {e}");
+                failed += 1;
+                first.get_or_insert(e);
+            }
+        }
+    }
+    match first {
+        None => Ok(()),
+        Some(first) => Err(Error::Inputs {
+            failed,
+            total: inputs.len(),
+            first: Box::new(first),
+        }),
+    }
 }
 
-fn get_image_matrix(input: String) -> std::result::Result<(), Box<dyn std::error::Error>> {
-    let mut img = ImageReader::open(&input)?.with_guessed_format()?.decode()?;
-
-    img = fit_image(&img);
+fn view(cli: &Cli, args: &ViewArgs) -> Result<()> {
+    let to_terminal = io::stdout().is_terminal();
+    let output = if to_terminal {
+        Output::Terminal
+    } else {
+        Output::Pipe
+    };
+    let opts = render_options(cli, &args.render, output)?;
+    let mut printed = false;
+    for_each_input(&input_paths(&args.input)?, |input| {
+        let heading = |printed: bool| -> Result<()> {
+            let mut stdout = io::stdout().lock();
+            if printed && let Some(separator) = &args.separator {
+                writeln!(stdout, "{separator}")?;
+            }
+            if args.header {
+                writeln!(stdout, "==> {} <==", input.display())?;
+            }
+            Ok(())
+        };
+        let mut show = |img: DynamicImage, bilevel: bool| -> Result<()> {
+            let opts = RenderOptions {
+                bilevel,
+                ..opts.clone()
+            };
+            let out = if args.sweep {
+                render::sweep(&img, &opts)
+            } else {
+                render::render(&img, &opts)
+            };
+            heading(printed)?;
+            io::stdout().lock().write_all(out.text.as_bytes())?;
+            printed = true;
+            Ok(())
+        };
+        match args.input.raw {
+            // Every frame of a raw stream is shown in turn.
+            Some(raw) => {
+                let mut reader = raw.open(input)?;
+                while let Some(img) = raw.read_frame(&mut reader, input)? {
+                    show(img, false)?;
+                }
+                Ok(())
+            }
+            // Animations are played in place when there is a terminal to
+            // play them in.
+            None => {
+                let mut reader = input::open(input)?;
+                if to_terminal && !args.sweep {
+                    match anim::open(reader, input)? {
+                        anim::Input::Animation(animation) => {
+                            heading(printed)?;
+                            printed = true;
+                            return animation.play(&opts, args.loops);
+                        }
+                        anim::Input::Image(image) => reader = image,
+                    }
+                }
+                let page = args.input.page.get();
+                let icon_size = args.input.icon_size.map(NonZeroU32::get);
+                let (img, bilevel) = load_image(reader, input, page, icon_size, &opts)?;
+                show(img, bilevel)
+            }
+        }
+    })
+}
 
-    let gray = to_grayscale_luma8(img);
-    let invert = env::args().nth(2).as_deref() == Some("invert");
+fn convert(cli: &Cli, args: &ConvertArgs) -> Result<()> {
+    let inputs = input_paths(&args.input)?;
+    if args.output.is_some() && inputs.len() > 1 {
+        Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--output can only be used with a single input",
+            )
+            .exit();
+    }
 
-    let t = otsu_threshold(&gray);
+    if args.output.is_none() && !inputs.iter().all(|input| input::is_file(input)) {
+        Cli::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "--output is required to convert the standard input, a URL or a data URI",
+            )
+            .exit();
+    }
 
-    let (w, h) = gray.dimensions();
-    for y in (0..h).step_by(4) {
-        let mut line = String::with_capacity((w as usize / 2) + 8);
-        for x in (0..w).step_by(2) {
-            let mut bits: u8 = 0;
+    let opts = render_options(cli, &args.render, Output::File)?;
+    for_each_input(&inputs, |input| {
+        let (img, bilevel) = match args.input.raw {
+            Some(raw) => (raw.load(input)?, false),
+            None => load_image(
+                input::open(input)?,
+                input,
+                args.input.page.get(),
+                args.input.icon_size.map(NonZeroU32::get),
+                &opts,
+            )?,
+        };
+        let opts = RenderOptions {
+            bilevel,
+            ..opts.clone()
+        };
+        let out = render::render(&img, &opts);
+        let output = match &args.output {
+            Some(path) => path.clone(),
+            None => input.with_extension("txt"),
+        };
+        fs::write(&output, out.text).map_err(|e| Error::Io(output, e))?;
+        Ok(())
+    })
+}
 
-            bits |= bit_if_on(&gray, x, y, t, invert);
-            bits |= bit_if_on(&gray, x, y + 1, t, invert) << 1;
-            bits |= bit_if_on(&gray, x, y + 2, t, invert) << 2;
-            bits |= bit_if_on(&gray, x + 1, y, t, invert) << 3;
-            bits |= bit_if_on(&gray, x + 1, y + 1, t, invert) << 4;
-            bits |= bit_if_on(&gray, x + 1, y + 2, t, invert) << 5;
-            bits |= bit_if_on(&gray, x, y + 3, t, invert) << 6;
-            bits |= bit_if_on(&gray, x + 1, y + 3, t, invert) << 7;
+fn info(args: &InfoArgs) -> Result<()> {
+    // Images are decoded the way the other commands do with default options,
+    // so that every format is read and photos are turned upright.
+    let opts = RenderOptions::from(&RenderArgs::default());
+    let mut printed = false;
+    for_each_input(&input_paths(&args.input)?, |input| {
+        let (format, img) = match args.input.raw {
+            Some(raw) => (format!("raw {:?}", raw.pixels), raw.load(input)?),
+            None => {
+                let reader = input::open(input)?;
+                let format = match reader.format() {
+                    Some(format) => format!("{format:?}"),
+                    None => "unknown".to_string(),
+                };
+                let page = args.input.page.get();
+                let icon_size = args.input.icon_size.map(NonZeroU32::get);
+                let (img, _) = decode(reader, input, page, icon_size, &opts)?;
+                (format, img)
+            }
+        };
 
-            let ch = char::from_u32(0x2800 + bits as u32).unwrap_or('\u{2800}');
-            line.push(ch);
+        if printed {
+            println!();
         }
-        println!("{line}");
+        println!("File:       {}", input.display());
+        println!("Format:     {format}");
+        println!("Dimensions: {}x{}", img.width(), img.height());
+        println!("Color type: {:?}", img.color());
+        printed = true;
+        Ok(())
+    })
+}
+
+fn play(cli: &Cli, args: &PlayArgs) -> Result<()> {
+    let output = if io::stdout().is_terminal() {
+        Output::Terminal
+    } else {
+        Output::Pipe
+    };
+    let opts = render_options(cli, &args.render, output)?;
+    match args.raw {
+        Some(raw) => anim::play_raw(&args.input, raw, &opts),
+        None => anim::play(&args.input, &opts, args.loops),
     }
+}
 
+fn completions(args: &CompletionsArgs) -> Result<()> {
+    let mut cmd = Cli::command();
+    let name = cmd.get_name().to_string();
+    clap_complete::generate(args.shell, &mut cmd, name, &mut io::stdout());
     Ok(())
 }
 
-fn main() {
-    let mut args = env::args().skip(1);
-    let input = args.next().expect("Usage: climg <input-image> [invert]");
+fn main() -> ExitCode {
+    let cli = Cli::parse_with_default_command();
+    logging::init(cli.verbose, cli.quiet);
+    jxl::register();
 
-    if let Err(e) = get_image_matrix(input) {
-        eprintln!("Error processing image: {}", e);
+    let result = match &cli.command {
+        Command::View(args) => view(&cli, args),
+        Command::Convert(args) => convert(&cli, args),
+        Command::Info(args) => info(args),
+        Command::Play(args) => play(&cli, args),
+        Command::Completions(args) => completions(args),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("climg: {e}");
+            e.exit_code()
+        }
     }
 }
@@ -0,0 +1,254 @@
+use crate::cli::RenderArgs;
+use crate::dicom;
+use crate::fit::{Filter, FitMode, Resize};
+use crate::render::{
+    AnsiPalette, Background, Channel, Charset, ColorDepth, Crop, Deficiency, Dither, Edges,
+    Grayscale, Levels, Overlay, Palette, Position, RenderMode, ThresholdMode, Tiles, Tonemap,
+};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::env;
+use std::num::NonZeroU16;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// When the color modes may emit colors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorChoice {
+    /// Only when writing to a terminal or a file and NO_COLOR is not set
+    #[default]
+    Auto,
+    /// Always
+    Always,
+    /// Never; the color modes fall back to monochrome braille
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether colors are allowed for output that does or does not get them
+    /// by default.
+    pub fn enabled(self, colored: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => colored && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+        }
+    }
+}
+
+/// Settings that control how an image is turned into terminal output.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub mode: RenderMode,
+    pub invert: bool,
+    /// Manual threshold; `None` picks one with Otsu's method.
+    pub threshold: Option<u8>,
+    /// How colors are turned into brightness.
+    pub grayscale: Grayscale,
+    /// Draw the outlines found with this method instead of the brightness.
+    pub edges: Option<Edges>,
+    /// Hysteresis cutoffs of --edges canny, `edge_lo <= edge_hi`.
+    pub edge_lo: u8,
+    pub edge_hi: u8,
+    /// Angle of the light --emboss draws the relief with.
+    pub emboss: Option<f32>,
+    /// Draw line art instead, with the threshold and sharpness of the XDoG.
+    pub sketch: bool,
+    pub sketch_threshold: f32,
+    pub sketch_sharpness: f32,
+    /// Percentage of the pixels the threshold is picked to light.
+    pub threshold_percentile: Option<f32>,
+    /// How the threshold is picked when it is not fixed.
+    pub threshold_mode: ThresholdMode,
+    /// Window size in pixels of the local thresholds, offset of the adaptive
+    /// one and k of Sauvola's and Niblack's.
+    pub threshold_window: u32,
+    pub threshold_offset: i16,
+    pub threshold_k: f32,
+    /// Grid of --threshold-mode tiled-otsu.
+    pub threshold_tiles: Tiles,
+    /// Lower and upper cutoff of hysteresis thresholding, which replaces the
+    /// threshold and dithering when set.
+    pub hysteresis: Option<(u8, u8)>,
+    /// Turn photos upright according to their EXIF orientation.
+    pub exif_rotate: bool,
+    /// Clockwise rotation in degrees, between 0 and 360.
+    pub rotate: f32,
+    /// Mirror the image left to right and top to bottom, after the rotation.
+    pub flip_h: bool,
+    pub flip_v: bool,
+    /// Region of the image to draw, after the rotation and flips.
+    pub crop: Option<Crop>,
+    /// Channel drawn as grayscale instead of the image's colors.
+    pub channel: Option<Channel>,
+    /// Image drawn over the main one, loaded from --overlay, where and how
+    /// opaque.
+    pub overlay: Option<Overlay>,
+    pub overlay_pos: Position,
+    pub overlay_alpha: f32,
+    /// How HDR images are brought into the displayable range, after being
+    /// brightened by `exposure` stops.
+    pub tonemap: Tonemap,
+    pub exposure: f32,
+    /// Stored values of DICOM images shown from black to white, instead of
+    /// the ones the file suggests.
+    pub dicom_window: Option<dicom::Window>,
+    /// Input range mapped to the full range before rendering.
+    pub levels: Option<Levels>,
+    /// Brightness and contrast changes before rendering, -100 to 100.
+    pub brightness: i8,
+    pub contrast: i8,
+    /// Saturation factor, 1 leaves the colors alone, and hue rotation in
+    /// degrees, between 0 and 360.
+    pub saturation: f32,
+    pub hue_rotate: f32,
+    /// Gaussian blur sigma and unsharp mask amount before rendering.
+    pub blur: Option<f32>,
+    pub sharpen: Option<f32>,
+    /// Levels per color channel before rendering.
+    pub posterize: Option<u8>,
+    /// Color vision deficiency the colors are shown as seen with.
+    pub simulate: Option<Deficiency>,
+    /// Radius of the median filter run before thresholding.
+    pub denoise: Option<u32>,
+    /// Percentage of the pixels --autocontrast ignores at either end.
+    pub autocontrast: Option<f32>,
+    /// Equalize the histogram before thresholding.
+    pub equalize: bool,
+    /// Clip limit and grid of --clahe.
+    pub clahe: Option<f32>,
+    pub clahe_tiles: Tiles,
+    /// Gamma correction before thresholding, 1 leaves the image alone.
+    pub gamma: f32,
+    /// How the braille modes turn brightness into dots.
+    pub dither: Dither,
+    /// Size of the matrix of --dither bayer: 2, 4, 8 or 16.
+    pub bayer_size: u32,
+    /// Alternate the scan direction of error diffusion from row to row.
+    pub serpentine: bool,
+    /// Share of the error or noise dithering applies, between 0 and 1.
+    pub dither_strength: f32,
+    /// Seed of --dither random.
+    pub seed: u64,
+    /// Output size in character cells. When only one is set the other
+    /// follows from the image's aspect ratio; when both are set the image
+    /// is stretched to exactly that grid.
+    pub width: Option<NonZeroU16>,
+    pub height: Option<NonZeroU16>,
+    /// Scaling mode; `None` derives one from which sizes were given.
+    pub fit: Option<FitMode>,
+    /// Pick the region --fit cover keeps by its detail instead of the center.
+    pub smart_crop: bool,
+    /// How --fit stretch and cover change the aspect ratio.
+    pub resize: Resize,
+    /// Interpolation used to scale the image.
+    pub filter: Filter,
+    /// Brightness ramp for the ascii mode.
+    pub charset: Charset,
+    /// Palette size for the sixel mode.
+    pub sixel_colors: u16,
+    /// Colors the text modes may use, and whether to dither to them.
+    pub colors: ColorDepth,
+    pub color_dither: bool,
+    /// What the terminal's 16 standard colors look like.
+    pub ansi_colors: AnsiPalette,
+    /// Colors the output is snapped to, from --palette or --palette-file.
+    pub palette: Option<Vec<[u8; 3]>>,
+    /// What transparent pixels are drawn against.
+    pub bg: Background,
+    /// Draw transparency as a checkerboard in the color modes.
+    pub checker: bool,
+    /// Wrap the graphics protocols' escape sequences for tmux.
+    pub tmux: bool,
+    /// Whether the image comes from a PBM or other 1-bit source, whose
+    /// pixels the braille modes draw as dots. Set for each input once it is
+    /// decoded.
+    pub bilevel: bool,
+}
+
+/// A seed that differs from run to run.
+pub fn random_seed() -> u64 {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    time ^ ((process::id() as u64) << 32)
+}
+
+impl From<&RenderArgs> for RenderOptions {
+    fn from(args: &RenderArgs) -> Self {
+        let threshold_mode = args.threshold_mode.unwrap_or_default();
+        let (edge_lo, edge_hi) = (args.edge_lo.unwrap_or(20), args.edge_hi.unwrap_or(50));
+        Self {
+            mode: args.mode.unwrap_or_default(),
+            invert: args.invert.unwrap_or(false),
+            threshold: args.threshold,
+            grayscale: args.grayscale.unwrap_or_default(),
+            edges: args.edges,
+            edge_lo: edge_lo.min(edge_hi),
+            edge_hi: edge_hi.max(edge_lo),
+            emboss: args.emboss,
+            sketch: args.sketch.unwrap_or(false),
+            sketch_threshold: args.sketch_threshold.unwrap_or(0.0).clamp(0.0, 1.0),
+            sketch_sharpness: args.sketch_sharpness.filter(|s| *s > 0.0).unwrap_or(10.0),
+            threshold_percentile: args.threshold_percentile.map(|p| p.clamp(0.0, 100.0)),
+            threshold_mode,
+            threshold_window: args.threshold_window.map_or(15, |w| w.get() as u32),
+            threshold_offset: args.threshold_offset.unwrap_or(10),
+            threshold_k: args.threshold_k.unwrap_or(threshold_mode.default_k()),
+            threshold_tiles: args.threshold_tiles.unwrap_or_default(),
+            hysteresis: args
+                .threshold_lo
+                .zip(args.threshold_hi)
+                .map(|(lo, hi)| (lo.min(hi), hi.max(lo))),
+            exif_rotate: args.exif_rotate.unwrap_or(true),
+            rotate: args.rotate.unwrap_or(0.0).rem_euclid(360.0),
+            flip_h: args.flip_h.unwrap_or(false),
+            flip_v: args.flip_v.unwrap_or(false),
+            crop: args.crop,
+            channel: args.channel,
+            overlay: None,
+            overlay_pos: args.overlay_pos.unwrap_or_default(),
+            overlay_alpha: args.overlay_alpha.unwrap_or(1.0).clamp(0.0, 1.0),
+            tonemap: args.tonemap.unwrap_or_default(),
+            exposure: args.exposure.filter(|e| e.is_finite()).unwrap_or(0.0),
+            dicom_window: args.dicom_window,
+            levels: args.levels,
+            brightness: args.brightness.unwrap_or(0).clamp(-100, 100),
+            contrast: args.contrast.unwrap_or(0).clamp(-100, 100),
+            saturation: args.saturation.filter(|s| *s >= 0.0).unwrap_or(1.0),
+            hue_rotate: args.hue_rotate.unwrap_or(0.0).rem_euclid(360.0),
+            blur: args.blur.filter(|s| *s > 0.0),
+            sharpen: args.sharpen.filter(|a| *a > 0.0),
+            posterize: args.posterize.filter(|l| *l >= 2),
+            simulate: args.simulate,
+            denoise: args.denoise.map(|r| r.clamp(1, 16)),
+            autocontrast: args.autocontrast.map(|p| p.clamp(0.0, 100.0)),
+            equalize: args.equalize.unwrap_or(false),
+            clahe: args.clahe,
+            clahe_tiles: args.clahe_tiles.unwrap_or(Tiles { cols: 8, rows: 8 }),
+            gamma: args.gamma.filter(|g| *g > 0.0).unwrap_or(1.0),
+            dither: args.dither.unwrap_or_default(),
+            bayer_size: args.bayer_size.unwrap_or(4),
+            serpentine: args.serpentine.unwrap_or(false),
+            dither_strength: args.dither_strength.unwrap_or(1.0).clamp(0.0, 1.0),
+            seed: args.seed.unwrap_or_else(random_seed),
+            width: args.width,
+            height: args.height,
+            fit: args.fit,
+            smart_crop: args.smart_crop.unwrap_or(false),
+            resize: args.resize.unwrap_or_default(),
+            filter: args.filter.unwrap_or_default(),
+            charset: args.charset.clone().unwrap_or_default(),
+            sixel_colors: args.sixel_colors.unwrap_or(256),
+            colors: args.colors.unwrap_or_default(),
+            color_dither: args.color_dither.unwrap_or(false),
+            ansi_colors: args.ansi_colors.clone().unwrap_or_default(),
+            palette: args.palette.map(Palette::colors),
+            bg: args.bg.unwrap_or_default(),
+            checker: args.checker.unwrap_or(false),
+            tmux: false,
+            bilevel: false,
+        }
+    }
+}
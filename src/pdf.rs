@@ -0,0 +1,89 @@
+use crate::error::{Error, Result};
+use image::error::ImageFormatHint;
+use image::{DynamicImage, ImageError};
+use std::path::Path;
+
+/// Whether the data starts like a PDF document.
+pub fn is_pdf(head: &[u8]) -> bool {
+    head.starts_with(b"%PDF-")
+}
+
+/// Rasterizes page `page`, counted from 1, of the PDF document at the size
+/// the output grid needs, on a white sheet.
+#[cfg(feature = "pdf")]
+pub fn rasterize(
+    data: Vec<u8>,
+    page: usize,
+    input: &Path,
+    scale: impl Fn(f32, f32) -> f32,
+) -> Result<DynamicImage> {
+    use hayro::hayro_interpret::InterpreterSettings;
+    use hayro::hayro_syntax::{LoadPdfError, Pdf};
+    use hayro::vello_cpu::color::palette::css::WHITE;
+    use hayro::{PixmapSettings, RenderCache, RenderSettings};
+    use image::RgbaImage;
+    use image::error::DecodingError;
+
+    /// Largest side of the raster, to keep odd documents from exhausting the
+    /// memory.
+    const MAX_SIDE: f32 = 8192.0;
+
+    let fail = |reason: String| {
+        let hint = ImageFormatHint::Name("PDF".to_string());
+        Error::image(
+            input,
+            ImageError::Decoding(DecodingError::new(hint, reason)),
+        )
+    };
+    let pdf = Pdf::new(data).map_err(|e| {
+        fail(match e {
+            LoadPdfError::Decryption(_) => "the document is encrypted".to_string(),
+            LoadPdfError::Invalid => "the document is damaged".to_string(),
+        })
+    })?;
+    let pages = pdf.pages();
+    let page = page
+        .checked_sub(1)
+        .and_then(|i| pages.get(i))
+        .ok_or_else(|| {
+            fail(format!(
+                "there is no page {page}, the document has {}",
+                pages.len()
+            ))
+        })?;
+    let (width, height) = page.render_dimensions();
+    let scale = scale(width, height).min(MAX_SIDE / width.max(height));
+    log::info!("rasterizing {width}x{height} PDF page at {scale:.2}x");
+    let pixmap = hayro::render(
+        page,
+        &RenderCache::new(),
+        &InterpreterSettings::default(),
+        &RenderSettings::default(),
+        &PixmapSettings {
+            x_scale: scale,
+            y_scale: scale,
+            bg_color: WHITE,
+        },
+    );
+    // The sheet is opaque, so the premultiplied pixels are the plain ones.
+    let rgba = pixmap.data().iter().flat_map(|p| [p.r, p.g, p.b, p.a]);
+    let (width, height) = (pixmap.width() as u32, pixmap.height() as u32);
+    let rgba = RgbaImage::from_raw(width, height, rgba.collect()).expect("one pixel each");
+    Ok(DynamicImage::ImageRgba8(rgba))
+}
+
+#[cfg(not(feature = "pdf"))]
+pub fn rasterize(
+    _: Vec<u8>,
+    _: usize,
+    input: &Path,
+    _: impl Fn(f32, f32) -> f32,
+) -> Result<DynamicImage> {
+    use image::error::{UnsupportedError, UnsupportedErrorKind};
+
+    log::warn!("climg was built without the `pdf` feature");
+    let hint = ImageFormatHint::Name("PDF".to_string());
+    let e =
+        UnsupportedError::from_format_and_kind(hint.clone(), UnsupportedErrorKind::Format(hint));
+    Err(Error::image(input, ImageError::Unsupported(e)))
+}
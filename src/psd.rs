@@ -0,0 +1,187 @@
+use crate::error::{Error, Result};
+use image::error::{DecodingError, ImageFormatHint};
+use image::{DynamicImage, GrayAlphaImage, GrayImage, ImageError, RgbImage, RgbaImage};
+use std::path::Path;
+
+/// Whether the data starts like a Photoshop document.
+pub fn is_psd(head: &[u8]) -> bool {
+    head.starts_with(b"8BPS")
+}
+
+/// The big-endian fields of a document, read one after another.
+struct Fields<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.at..self.at.checked_add(len)?)?;
+        self.at += len;
+        Some(bytes)
+    }
+
+    fn int(&mut self, len: usize) -> Option<usize> {
+        let bytes = self.take(len)?;
+        usize::try_from(bytes.iter().fold(0u64, |v, b| (v << 8) | u64::from(*b))).ok()
+    }
+
+    /// A section preceded by its length, on 8 bytes when `long`.
+    fn section(&mut self, long: bool) -> Option<&'a [u8]> {
+        let len = self.int(if long { 8 } else { 4 })?;
+        self.take(len)
+    }
+}
+
+/// Appends the PackBits encoded row to `out`, padded or cut to `len` bytes.
+fn unpack(mut src: &[u8], len: usize, out: &mut Vec<u8>) {
+    let end = out.len() + len;
+    while let [n, rest @ ..] = src {
+        let n = *n as i8;
+        src = match (n, rest) {
+            (0.., _) => {
+                let literal = (n as usize + 1).min(rest.len());
+                out.extend_from_slice(&rest[..literal]);
+                &rest[literal..]
+            }
+            (-128, _) => rest,
+            (_, [b, rest @ ..]) => {
+                out.extend(std::iter::repeat_n(*b, 1 + n.unsigned_abs() as usize));
+                rest
+            }
+            (_, []) => break,
+        };
+        if out.len() >= end {
+            break;
+        }
+    }
+    out.resize(end, 0);
+}
+
+/// Decodes the flattened composite Photoshop keeps next to the layers of PSD
+/// and PSB documents, in bitmap, grayscale, duotone, indexed, RGB or CMYK
+/// color with 1, 8 or 16 bits per channel. Documents saved without maximized
+/// compatibility hold an empty composite.
+pub fn decode(data: &[u8], input: &Path) -> Result<DynamicImage> {
+    let fail = |reason: &str| {
+        let hint = ImageFormatHint::Name("PSD".to_string());
+        Error::image(
+            input,
+            ImageError::Decoding(DecodingError::new(hint, reason.to_string())),
+        )
+    };
+    let damaged = || fail("the document is damaged");
+
+    let mut fields = Fields { data, at: 4 };
+    let mut int = |len| fields.int(len).ok_or_else(damaged);
+    let long = int(2)? == 2;
+    int(6)?;
+    let (channels, height, width, depth, mode) = (int(2)?, int(4)?, int(4)?, int(2)?, int(2)?);
+    let palette = fields.section(false).ok_or_else(damaged)?;
+    fields.section(false).ok_or_else(damaged)?;
+    // A negative layer count means the first extra channel holds the
+    // transparency of the composite.
+    let layers = fields.section(long).ok_or_else(damaged)?;
+    let alpha = Fields {
+        data: layers,
+        at: 0,
+    }
+    .section(long)
+    .and_then(|info| info.get(..2))
+    .is_some_and(|count| i16::from_be_bytes([count[0], count[1]]) < 0);
+
+    let colors = match (mode, depth) {
+        (0, 1) | (1 | 8, 8 | 16) | (2, 8) => 1,
+        (3, 8 | 16) => 3,
+        (4, 8 | 16) => 4,
+        (0 | 1 | 2 | 3 | 4 | 8, _) => return Err(fail("the bit depth is not supported")),
+        _ => return Err(fail("the color mode is not supported")),
+    };
+    let used = colors + usize::from(alpha && mode != 2);
+    let row = (width * depth).div_ceil(8);
+    // Even run-length encoded, a channel takes at least one byte per 64.
+    if used > channels || row.saturating_mul(height) > data.len().saturating_mul(64) {
+        return Err(damaged());
+    }
+
+    let compression = fields.int(2).ok_or_else(damaged)?;
+    let planes: Vec<Vec<u8>> = match compression {
+        0 => (0..used)
+            .map(|_| fields.take(row * height).map(<[u8]>::to_vec))
+            .collect::<Option<_>>()
+            .ok_or_else(damaged)?,
+        1 => {
+            let counts = (0..channels * height)
+                .map(|_| fields.int(if long { 4 } else { 2 }))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(damaged)?;
+            let mut planes = Vec::new();
+            for counts in counts.chunks(height.max(1)).take(used) {
+                let mut plane = Vec::with_capacity(row * height);
+                for &count in counts {
+                    unpack(fields.take(count).ok_or_else(damaged)?, row, &mut plane);
+                }
+                planes.push(plane);
+            }
+            planes
+        }
+        _ => {
+            return Err(fail(
+                "only raw and run-length encoded documents are supported",
+            ));
+        }
+    };
+    log::info!(
+        "decoding {width}x{height} composite of {channels} channels, color mode {mode}, \
+         {depth} bits"
+    );
+
+    // The 8-bit value of a channel, with set bitmap bits standing for black.
+    let at = |c: usize, x: u32, y: u32| {
+        let (plane, x) = (&planes[c][y as usize * row..], x as usize);
+        match depth {
+            1 if (plane[x / 8] << (x % 8)) & 0x80 != 0 => 0,
+            1 => 255,
+            8 => plane[x],
+            _ => plane[x * 2],
+        }
+    };
+    let (w, h) = (width as u32, height as u32);
+    Ok(match (mode, used) {
+        (0 | 1 | 8, 1) => {
+            DynamicImage::ImageLuma8(GrayImage::from_fn(w, h, |x, y| [at(0, x, y)].into()))
+        }
+        (0 | 1 | 8, _) => DynamicImage::ImageLumaA8(GrayAlphaImage::from_fn(w, h, |x, y| {
+            [at(0, x, y), at(1, x, y)].into()
+        })),
+        (2, _) => {
+            let palette = palette.get(..768).ok_or_else(damaged)?;
+            DynamicImage::ImageRgb8(RgbImage::from_fn(w, h, |x, y| {
+                let i = at(0, x, y) as usize;
+                [palette[i], palette[256 + i], palette[512 + i]].into()
+            }))
+        }
+        (3, 3) => DynamicImage::ImageRgb8(RgbImage::from_fn(w, h, |x, y| {
+            [at(0, x, y), at(1, x, y), at(2, x, y)].into()
+        })),
+        (3, _) => DynamicImage::ImageRgba8(RgbaImage::from_fn(w, h, |x, y| {
+            [at(0, x, y), at(1, x, y), at(2, x, y), at(3, x, y)].into()
+        })),
+        // CMYK is stored as the paper left uncovered by each ink.
+        _ => {
+            let rgb = |x, y| {
+                let k = u16::from(at(3, x, y));
+                let c = |c| (u16::from(at(c, x, y)) * k / 255) as u8;
+                [c(0), c(1), c(2)]
+            };
+            if used == 4 {
+                DynamicImage::ImageRgb8(RgbImage::from_fn(w, h, |x, y| rgb(x, y).into()))
+            } else {
+                DynamicImage::ImageRgba8(RgbaImage::from_fn(w, h, |x, y| {
+                    let [r, g, b] = rgb(x, y);
+                    [r, g, b, at(4, x, y)].into()
+                }))
+            }
+        }
+    })
+}
@@ -0,0 +1,264 @@
+mod adjust;
+mod ascii;
+mod blocks;
+mod braille;
+mod cells;
+mod color;
+mod dither;
+mod edges;
+mod emoji;
+mod glyph;
+mod grays;
+mod halftone;
+mod iterm;
+mod kitty;
+mod octants;
+mod overlay;
+mod palette;
+mod quadrants;
+mod sextants;
+mod shades;
+mod sixel;
+mod sweep;
+mod symbols;
+mod threshold;
+mod tmux;
+mod tonemap;
+mod transform;
+
+use crate::fit::{self, fit_image};
+use crate::options::RenderOptions;
+use clap::ValueEnum;
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use serde::Deserialize;
+use std::io::Cursor;
+
+pub use adjust::{Channel, Deficiency, Grayscale, Levels};
+pub use ascii::Charset;
+pub use color::{AnsiPalette, Background, ColorDepth};
+pub use dither::Dither;
+pub use edges::Edges;
+pub use overlay::{Overlay, Position};
+pub use palette::{Palette, load_palette};
+pub use sweep::sweep;
+pub use threshold::{ThresholdMode, Tiles};
+pub use tonemap::Tonemap;
+pub use transform::Crop;
+
+/// Which characters the image is drawn with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RenderMode {
+    /// The best mode the terminal supports: kitty, sixel, iterm, blocks or
+    /// braille, in that order
+    #[default]
+    Auto,
+    /// Monochrome braille dots, 2x4 pixels per cell
+    Braille,
+    /// Braille dots in the 24-bit color of the lit pixels, 2x4 pixels per cell
+    ColorBraille,
+    /// Truecolor upper half blocks, 1x2 pixels per cell
+    Blocks,
+    /// Truecolor quadrant blocks, 2x2 pixels per cell
+    Quadrants,
+    /// Truecolor sextant blocks (Unicode 13), 2x3 pixels per cell
+    Sextants,
+    /// Truecolor octant blocks (Unicode 16), 2x4 pixels per cell
+    Octants,
+    /// The best fitting block, shade or braille character and color pair for
+    /// each cell, 4x8 pixels per cell
+    Symbols,
+    /// DEC sixel graphics with the real pixels of the image
+    Sixel,
+    /// The kitty graphics protocol with the real pixels of the image
+    Kitty,
+    /// The iTerm2 inline image protocol with the real pixels of the image
+    Iterm,
+    /// The shade characters ░▒▓█ picked by brightness, 2x4 pixels per cell
+    Shades,
+    /// Shade characters in the truecolor pair that fits each cell best, 2x4
+    /// pixels per cell
+    ColorShades,
+    /// Cells filled with the 24 grays of the 256 color palette, 1x2 pixels
+    /// per cell
+    Grays,
+    /// Characters from a brightness ramp (see --charset), 2x4 pixels per cell
+    Ascii,
+    /// Braille cells lighting as many dots as they are bright, like a printed
+    /// halftone, 2x4 pixels per cell
+    Halftone,
+    /// A mosaic of emoji in the colors of the image, 2x2 pixels per emoji
+    Emoji,
+}
+
+impl RenderMode {
+    /// Width and height in pixels of the area one character cell stands for.
+    /// Modes that draw real pixels ask the terminal how big its cells are.
+    pub fn cell_size(self) -> (u32, u32) {
+        match self {
+            RenderMode::Auto | RenderMode::Braille | RenderMode::ColorBraille => (2, 4),
+            RenderMode::Blocks | RenderMode::Grays => (1, 2),
+            RenderMode::Quadrants => (2, 2),
+            RenderMode::Sextants => (2, 3),
+            RenderMode::Octants => (2, 4),
+            RenderMode::Symbols => (4, 8),
+            RenderMode::Sixel | RenderMode::Kitty | RenderMode::Iterm => fit::cell_pixels(),
+            RenderMode::Ascii
+            | RenderMode::Halftone
+            | RenderMode::Shades
+            | RenderMode::ColorShades => (2, 4),
+            RenderMode::Emoji => (1, 2),
+        }
+    }
+
+    /// The renderer drawing this mode.
+    pub fn renderer(self) -> &'static dyn Renderer {
+        match self {
+            // Auto is resolved before rendering, braille is the safe fallback.
+            RenderMode::Auto | RenderMode::Braille => &braille::Braille { color: false },
+            RenderMode::ColorBraille => &braille::Braille { color: true },
+            RenderMode::Blocks => &blocks::Blocks,
+            RenderMode::Quadrants => &quadrants::Quadrants,
+            RenderMode::Sextants => &sextants::Sextants,
+            RenderMode::Octants => &octants::Octants,
+            RenderMode::Symbols => &symbols::Symbols,
+            RenderMode::Sixel => &sixel::Sixel,
+            RenderMode::Kitty => &kitty::Kitty,
+            RenderMode::Iterm => &iterm::Iterm,
+            RenderMode::Shades => &shades::Shades { color: false },
+            RenderMode::ColorShades => &shades::Shades { color: true },
+            RenderMode::Grays => &grays::Grays,
+            RenderMode::Ascii => &ascii::Ascii,
+            RenderMode::Halftone => &halftone::Halftone,
+            RenderMode::Emoji => &emoji::Emoji,
+        }
+    }
+
+    /// Whether the mode draws braille dots.
+    pub fn is_braille(self) -> bool {
+        matches!(
+            self,
+            RenderMode::Auto | RenderMode::Braille | RenderMode::ColorBraille
+        )
+    }
+
+    /// Whether the mode draws in the terminal's foreground color alone, so
+    /// that which parts light up depends on --invert.
+    pub fn monochrome(self) -> bool {
+        matches!(
+            self,
+            RenderMode::Auto
+                | RenderMode::Braille
+                | RenderMode::Ascii
+                | RenderMode::Halftone
+                | RenderMode::Shades
+        )
+    }
+
+    /// Whether the mode writes colors, which NO_COLOR and --color turn off.
+    pub fn uses_colors(self) -> bool {
+        self.paints_cells() || self == RenderMode::Grays
+    }
+
+    /// Whether the mode sends real pixels through a graphics protocol.
+    pub fn draws_pixels(self) -> bool {
+        matches!(
+            self,
+            RenderMode::Sixel | RenderMode::Kitty | RenderMode::Iterm
+        )
+    }
+
+    /// Whether the mode colors character cells, which is what --colors and
+    /// --color-dither apply to.
+    pub fn paints_cells(self) -> bool {
+        matches!(
+            self,
+            RenderMode::ColorBraille
+                | RenderMode::Blocks
+                | RenderMode::Quadrants
+                | RenderMode::Sextants
+                | RenderMode::Octants
+                | RenderMode::Symbols
+                | RenderMode::ColorShades
+        )
+    }
+}
+
+/// What a renderer produced for one image.
+pub struct RenderedFrame {
+    /// Text and escape sequences to write to the terminal.
+    pub text: String,
+    /// Terminal rows the output takes up.
+    pub rows: u32,
+    /// Id of the image the kitty graphics protocol keeps for the output,
+    /// which stays in the terminal's memory until deleted.
+    pub image: Option<u32>,
+}
+
+impl From<String> for RenderedFrame {
+    /// Output made of lines of text takes up one row per line.
+    fn from(text: String) -> Self {
+        let rows = text.lines().count() as u32;
+        Self {
+            text,
+            rows,
+            image: None,
+        }
+    }
+}
+
+/// Draws an image that was already fitted to the output grid in one of the
+/// modes.
+pub trait Renderer {
+    fn render(&self, img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame;
+}
+
+/// Fits the image to the output grid of `cell` sized cells and applies the
+/// changes every mode shares.
+fn prepare(img: &DynamicImage, opts: &RenderOptions, cell: (u32, u32)) -> DynamicImage {
+    let img = tonemap::apply(img, opts);
+    let img = overlay::apply(transform::apply(&img, opts), opts);
+    let mut img = fit_image(&img, opts, cell);
+    if let Some(channel) = opts.channel {
+        img = adjust::channel(&img, channel);
+    }
+    if let Background::Color(bg) = opts.bg {
+        img = color::flatten(img, bg);
+    } else if opts.checker && !opts.mode.monochrome() {
+        // Squares one cell tall, whatever the mode's pixels per cell.
+        img = color::checker(img, cell.1);
+    }
+    adjust::image(img, opts)
+}
+
+/// Fits the image to the output grid and draws it in the selected mode.
+pub fn render(img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+    let mut img = prepare(img, opts, opts.mode.cell_size());
+    if opts.color_dither && opts.mode.paints_cells() {
+        img = color::dither(img, &color::ColorSpace::new(opts), opts);
+    }
+    let mut frame = opts.mode.renderer().render(&img, opts);
+    if opts.tmux && opts.mode.draws_pixels() {
+        frame.text = tmux::passthrough(&frame.text);
+    }
+    frame
+}
+
+/// The escape sequence deleting an image the kitty graphics protocol drew,
+/// from the screen and the terminal's memory.
+pub fn delete_image(id: u32, opts: &RenderOptions) -> String {
+    let text = kitty::delete(id);
+    if opts.tmux {
+        tmux::passthrough(&text)
+    } else {
+        text
+    }
+}
+
+/// The image as PNG, the format the terminal image protocols all accept.
+fn encode_png(img: &RgbaImage) -> Vec<u8> {
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .expect("encoding a PNG in memory does not fail");
+    png
+}
@@ -0,0 +1,517 @@
+use super::cells::ALPHA_CUTOFF;
+use super::threshold::Tiles;
+use crate::options::RenderOptions;
+use clap::ValueEnum;
+use image::{DynamicImage, GrayAlphaImage, LumaA};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// How colors are turned into the brightness the monochrome modes draw.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Grayscale {
+    /// Perceived brightness, weighting green most and blue least
+    #[default]
+    Luminosity,
+    /// The mean of red, green and blue
+    Average,
+    /// Halfway between the strongest and the weakest channel
+    Lightness,
+    /// The strongest channel
+    Max,
+    /// Only the red channel, e.g. for skin detail
+    Red,
+    /// Only the green channel, e.g. for foliage
+    Green,
+    /// Only the blue channel
+    Blue,
+}
+
+/// The brightness and alpha of every pixel, computed as `method` asks.
+pub fn grayscale(img: &DynamicImage, method: Grayscale) -> GrayAlphaImage {
+    let brightness: fn([u8; 3]) -> u8 = match method {
+        Grayscale::Luminosity => return img.to_luma_alpha8(),
+        Grayscale::Average => |[r, g, b]| ((r as u16 + g as u16 + b as u16 + 1) / 3) as u8,
+        Grayscale::Lightness => {
+            |[r, g, b]| (r.max(g).max(b) as u16 + r.min(g).min(b) as u16).div_ceil(2) as u8
+        }
+        Grayscale::Max => |[r, g, b]| r.max(g).max(b),
+        Grayscale::Red => |[r, _, _]| r,
+        Grayscale::Green => |[_, g, _]| g,
+        Grayscale::Blue => |[_, _, b]| b,
+    };
+    let rgba = img.to_rgba8();
+    GrayAlphaImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        LumaA([brightness([r, g, b]), a])
+    })
+}
+
+/// A single channel of the image, drawn as grayscale by --channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+pub enum Channel {
+    /// Red
+    #[value(name = "r")]
+    #[serde(rename = "r")]
+    Red,
+    /// Green
+    #[value(name = "g")]
+    #[serde(rename = "g")]
+    Green,
+    /// Blue
+    #[value(name = "b")]
+    #[serde(rename = "b")]
+    Blue,
+    /// Alpha, opaque pixels white and transparent ones black
+    #[value(name = "a")]
+    #[serde(rename = "a")]
+    Alpha,
+}
+
+/// An opaque gray image of one channel of `img`.
+pub fn channel(img: &DynamicImage, channel: Channel) -> DynamicImage {
+    let index = match channel {
+        Channel::Red => 0,
+        Channel::Green => 1,
+        Channel::Blue => 2,
+        Channel::Alpha => 3,
+    };
+    let mut rgba = img.to_rgba8();
+    for pixel in rgba.pixels_mut() {
+        let v = pixel[index];
+        pixel.0 = [v, v, v, 255];
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// A color vision deficiency --simulate shows the image as seen with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Deficiency {
+    /// No red cones, red and green look alike and reds dark
+    Protanopia,
+    /// No green cones, red and green look alike
+    Deuteranopia,
+    /// No blue cones, blue and green look alike and yellow pinkish
+    Tritanopia,
+}
+
+impl Deficiency {
+    /// The simulation matrix of Machado, Oliveira and Fernandes (2009) at
+    /// full severity, for linear RGB.
+    fn matrix(self) -> [[f32; 3]; 3] {
+        match self {
+            Deficiency::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            Deficiency::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            Deficiency::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+}
+
+/// The input range `--levels` stretches to the full range, given as
+/// `BLACK,WHITE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Levels {
+    pub black: u8,
+    pub white: u8,
+}
+
+impl FromStr for Levels {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let level = |n: &str| n.trim().parse::<u8>().ok();
+        match s.split_once(',').map(|(b, w)| (level(b), level(w))) {
+            Some((Some(black), Some(white))) if black < white => Ok(Levels { black, white }),
+            Some((Some(_), Some(_))) => Err(format!(
+                "the black point of `{s}` is not below the white point"
+            )),
+            _ => Err(format!("`{s}` is not a pair of levels such as `30,220`")),
+        }
+    }
+}
+
+impl TryFrom<String> for Levels {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Applies the color adjustments the options ask for to the fitted image,
+/// before any mode draws it.
+pub fn image(mut img: DynamicImage, opts: &RenderOptions) -> DynamicImage {
+    if let Some(levels) = opts.levels {
+        img = self::levels(img, levels);
+    }
+    if opts.brightness != 0 || opts.contrast != 0 {
+        img = brightness_contrast(img, opts.brightness, opts.contrast);
+    }
+    if opts.saturation != 1.0 || opts.hue_rotate != 0.0 {
+        img = saturation_hue(img, opts.saturation, opts.hue_rotate);
+    }
+    if let Some(sigma) = opts.blur {
+        log::info!("blur sigma={sigma}");
+        img = img.blur(sigma);
+    }
+    if let Some(amount) = opts.sharpen {
+        img = sharpen(img, amount);
+    }
+    if let Some(levels) = opts.posterize {
+        img = posterize(img, levels);
+    }
+    if let Some(deficiency) = opts.simulate {
+        img = simulate(img, deficiency);
+    }
+    img
+}
+
+/// Replaces every color channel value `v` with `map[v]`, leaving alpha alone.
+fn map_channels(img: DynamicImage, map: &[u8; 256]) -> DynamicImage {
+    let mut rgba = img.to_rgba8();
+    for pixel in rgba.pixels_mut() {
+        for c in &mut pixel.0[..3] {
+            *c = map[*c as usize];
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Maps `levels.black` and below to 0 and `levels.white` and above to 255
+/// in every color channel, stretching the values between linearly.
+fn levels(img: DynamicImage, levels: Levels) -> DynamicImage {
+    let Levels { black, white } = levels;
+    log::info!("levels black={black} white={white}");
+    let map = std::array::from_fn(|v| {
+        ((v as f32 - black as f32) * 255.0 / (white - black) as f32)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    });
+    map_channels(img, &map)
+}
+
+/// Shifts every color channel by `brightness` percent of the full range and
+/// scales its distance from the middle gray by a factor from 0 (flat gray at
+/// -100) through 1 (unchanged at 0) to 4 (at 100) for `contrast`.
+fn brightness_contrast(img: DynamicImage, brightness: i8, contrast: i8) -> DynamicImage {
+    log::info!("brightness={brightness} contrast={contrast}");
+    let shift = brightness as f32 * 255.0 / 100.0;
+    let factor = ((100.0 + contrast as f32) / 100.0).powi(2);
+    let map = std::array::from_fn(|v| {
+        ((v as f32 - 127.5) * factor + 127.5 + shift)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    });
+    map_channels(img, &map)
+}
+
+/// Applies the 3x3 `matrix` to the red, green and blue of every pixel,
+/// leaving alpha alone.
+fn color_matrix(img: DynamicImage, matrix: [[f32; 3]; 3]) -> DynamicImage {
+    let mut rgba = img.to_rgba8();
+    for pixel in rgba.pixels_mut() {
+        let rgb = [pixel[0], pixel[1], pixel[2]].map(|c| c as f32);
+        for (c, row) in pixel.0[..3].iter_mut().zip(matrix) {
+            let v: f32 = row.iter().zip(rgb).map(|(m, c)| m * c).sum();
+            *c = v.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Scales the saturation by `saturation` (0 is gray, 1 unchanged) and turns
+/// the hues by `degrees`, with the matrices of the CSS `saturate()` and
+/// `hue-rotate()` filters, which keep the luminance.
+fn saturation_hue(img: DynamicImage, saturation: f32, degrees: f32) -> DynamicImage {
+    log::info!("saturation={saturation} hue-rotate={degrees}");
+    const LUMA: [f32; 3] = [0.213, 0.715, 0.072];
+    let s = saturation;
+    let saturate: [[f32; 3]; 3] = std::array::from_fn(|row| {
+        std::array::from_fn(|col| LUMA[col] * (1.0 - s) + if row == col { s } else { 0.0 })
+    });
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let hue = [
+        [
+            0.213 + cos * 0.787 - sin * 0.213,
+            0.715 - cos * 0.715 - sin * 0.715,
+            0.072 - cos * 0.072 + sin * 0.928,
+        ],
+        [
+            0.213 - cos * 0.213 + sin * 0.143,
+            0.715 + cos * 0.285 + sin * 0.140,
+            0.072 - cos * 0.072 - sin * 0.283,
+        ],
+        [
+            0.213 - cos * 0.213 - sin * 0.787,
+            0.715 - cos * 0.715 + sin * 0.715,
+            0.072 + cos * 0.928 + sin * 0.072,
+        ],
+    ];
+    let matrix = std::array::from_fn(|row| {
+        std::array::from_fn(|col| (0..3).map(|k| hue[row][k] * saturate[k][col]).sum())
+    });
+    color_matrix(img, matrix)
+}
+
+/// Unsharp masking: pushes every color channel away from a blurred copy by
+/// `amount` times the difference, which makes fine detail such as small text
+/// stand out.
+fn sharpen(img: DynamicImage, amount: f32) -> DynamicImage {
+    log::info!("sharpen amount={amount}");
+    let blurred = img.blur(1.0).to_rgba8();
+    let mut rgba = img.to_rgba8();
+    for (pixel, soft) in rgba.pixels_mut().zip(blurred.pixels()) {
+        for (c, s) in pixel.0[..3].iter_mut().zip(soft.0) {
+            let v = *c as f32 + (*c as f32 - s as f32) * amount;
+            *c = v.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Shows the image the way people with `deficiency` see it, by applying its
+/// matrix to the linear light of every pixel.
+fn simulate(img: DynamicImage, deficiency: Deficiency) -> DynamicImage {
+    log::info!("simulate {deficiency:?}");
+    let linear: [f32; 256] = std::array::from_fn(|v| {
+        let c = v as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    let encode = |c: f32| {
+        let c = c.clamp(0.0, 1.0);
+        let v = if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        };
+        (v * 255.0).round() as u8
+    };
+    let matrix = deficiency.matrix();
+    let mut rgba = img.to_rgba8();
+    for pixel in rgba.pixels_mut() {
+        let rgb = [pixel[0], pixel[1], pixel[2]].map(|c| linear[c as usize]);
+        for (c, row) in pixel.0[..3].iter_mut().zip(matrix) {
+            *c = encode(row.iter().zip(rgb).map(|(m, c)| m * c).sum());
+        }
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Rounds every color channel to the nearest of `levels` evenly spaced
+/// values, for a flat color look.
+fn posterize(img: DynamicImage, levels: u8) -> DynamicImage {
+    log::info!("posterize levels={levels}");
+    let steps = (levels.max(2) - 1) as f32;
+    let map =
+        std::array::from_fn(|v| ((v as f32 * steps / 255.0).round() * 255.0 / steps).round() as u8);
+    map_channels(img, &map)
+}
+
+/// Applies the brightness adjustments the options ask for to the opaque
+/// pixels, before they are thresholded.
+pub fn luma(gray: &mut GrayAlphaImage, opts: &RenderOptions) {
+    if let Some(radius) = opts.denoise {
+        denoise(gray, radius);
+    }
+    if let Some(cutoff) = opts.autocontrast {
+        autocontrast(gray, cutoff);
+    }
+    if opts.equalize {
+        equalize(gray);
+    }
+    if let Some(clip) = opts.clahe {
+        clahe(gray, clip, opts.clahe_tiles);
+    }
+    if opts.gamma != 1.0 {
+        gamma(gray, opts.gamma);
+    }
+}
+
+/// Median filter: sets every opaque pixel to the median brightness of the
+/// opaque pixels within `radius` of it, which removes isolated specks of
+/// noise while keeping edges sharp.
+fn denoise(gray: &mut GrayAlphaImage, radius: u32) {
+    log::info!("denoise radius={radius}");
+    let src = gray.clone();
+    let (w, h) = src.dimensions();
+    let mut window = Vec::with_capacity(((2 * radius + 1) * (2 * radius + 1)) as usize);
+    for (x, y, pixel) in gray.enumerate_pixels_mut() {
+        if pixel[1] < ALPHA_CUTOFF {
+            continue;
+        }
+        window.clear();
+        for ny in y.saturating_sub(radius)..(y + radius + 1).min(h) {
+            for nx in x.saturating_sub(radius)..(x + radius + 1).min(w) {
+                let LumaA([v, a]) = *src.get_pixel(nx, ny);
+                if a >= ALPHA_CUTOFF {
+                    window.push(v);
+                }
+            }
+        }
+        let middle = window.len() / 2;
+        pixel[0] = *window.select_nth_unstable(middle).1;
+    }
+}
+
+/// How many of the opaque pixels there are of every brightness.
+fn histogram(gray: &GrayAlphaImage) -> [u32; 256] {
+    let mut hist = [0u32; 256];
+    for LumaA([v, a]) in gray.pixels() {
+        if *a >= ALPHA_CUTOFF {
+            hist[*v as usize] += 1;
+        }
+    }
+    hist
+}
+
+/// Replaces the brightness of every opaque pixel with `map[v]`.
+fn remap(gray: &mut GrayAlphaImage, map: &[u8; 256]) {
+    for pixel in gray.pixels_mut() {
+        if pixel[1] >= ALPHA_CUTOFF {
+            pixel[0] = map[pixel[0] as usize];
+        }
+    }
+}
+
+/// The first of `values` with more than `clip` pixels at it or before it.
+fn edge(hist: &[u32; 256], clip: u32, mut values: impl Iterator<Item = usize>) -> Option<usize> {
+    let mut seen = 0;
+    values.find(|&v| {
+        seen += hist[v];
+        seen > clip
+    })
+}
+
+/// Stretches the brightness linearly so that the darkest pixels become
+/// black and the brightest white, ignoring `cutoff` percent of the pixels
+/// at either end.
+fn autocontrast(gray: &mut GrayAlphaImage, cutoff: f32) {
+    let hist = histogram(gray);
+    let total: u32 = hist.iter().sum();
+    let clip = (total as f64 * cutoff.min(50.0) as f64 / 100.0) as u32;
+    let (Some(lo), Some(hi)) = (edge(&hist, clip, 0..256), edge(&hist, clip, (0..256).rev()))
+    else {
+        return;
+    };
+    log::info!("autocontrast cutoff={cutoff}% range={lo}-{hi}");
+    if hi <= lo {
+        return;
+    }
+    let map = std::array::from_fn(|v| {
+        ((v as f32 - lo as f32) * 255.0 / (hi - lo) as f32)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    });
+    remap(gray, &map);
+}
+
+/// Raises the brightness to the power of `1 / gamma`, which brightens the
+/// midtones for values above 1 and darkens them below, leaving black and
+/// white alone.
+fn gamma(gray: &mut GrayAlphaImage, gamma: f32) {
+    log::info!("gamma={gamma}");
+    let map = std::array::from_fn(|v| ((v as f32 / 255.0).powf(1.0 / gamma) * 255.0).round() as u8);
+    remap(gray, &map);
+}
+
+/// Global histogram equalization: spreads the brightness so that every level
+/// is about equally common, which brings out detail in both the shadows and
+/// the highlights.
+fn equalize(gray: &mut GrayAlphaImage) {
+    remap(gray, &equalization(&histogram(gray)));
+}
+
+/// The mapping that equalizes `hist`, from its cumulative distribution.
+fn equalization(hist: &[u32; 256]) -> [u8; 256] {
+    let total: u32 = hist.iter().sum();
+    let first = hist.iter().copied().find(|&h| h > 0).unwrap_or(0);
+    let mut seen = 0;
+    std::array::from_fn(|v| {
+        seen += hist[v];
+        if total <= first {
+            return v as u8;
+        }
+        (seen.saturating_sub(first) as f64 * 255.0 / (total - first) as f64).round() as u8
+    })
+}
+
+/// Contrast limited adaptive histogram equalization: equalizes every tile
+/// of the grid on its own, with no brightness taking more than `clip` times
+/// its fair share of a tile so noise is not blown up, and blends the
+/// mappings of the tiles around each pixel.
+fn clahe(gray: &mut GrayAlphaImage, clip: f32, tiles: Tiles) {
+    let dims = gray.dimensions();
+    let tiles = tiles.fit(dims);
+    log::info!("clahe clip={clip} tiles={}x{}", tiles.cols, tiles.rows);
+    let maps: Vec<[u8; 256]> = (0..tiles.rows)
+        .flat_map(|row| (0..tiles.cols).map(move |col| (col, row)))
+        .map(|(col, row)| {
+            let mut hist = [0u32; 256];
+            for (x, y) in tiles.pixels(col, row, dims) {
+                let LumaA([v, a]) = *gray.get_pixel(x, y);
+                if a >= ALPHA_CUTOFF {
+                    hist[v as usize] += 1;
+                }
+            }
+            clip_histogram(&mut hist, clip);
+            contrast_limited(&hist)
+        })
+        .collect();
+
+    for (x, y, pixel) in gray.enumerate_pixels_mut() {
+        if pixel[1] < ALPHA_CUTOFF {
+            continue;
+        }
+        let v = pixel[0] as usize;
+        let blended = tiles.blend(x, y, dims, |c, r| {
+            maps[(r * tiles.cols + c) as usize][v] as f32
+        });
+        pixel[0] = blended.round() as u8;
+    }
+}
+
+/// Caps every bin at `clip` times the average and spreads what was cut off
+/// evenly over all bins.
+fn clip_histogram(hist: &mut [u32; 256], clip: f32) {
+    let total: u32 = hist.iter().sum();
+    let limit = ((clip * total as f32 / 256.0) as u32).max(1);
+    let mut excess = 0;
+    for h in hist.iter_mut() {
+        excess += h.saturating_sub(limit);
+        *h = (*h).min(limit);
+    }
+    let (share, rest) = (excess / 256, excess % 256);
+    for (i, h) in hist.iter_mut().enumerate() {
+        *h += share + u32::from((i as u32) < rest);
+    }
+}
+
+/// The mapping of a clipped histogram to its cumulative distribution. Unlike
+/// [`equalization`], it does not stretch the darkest level to black, which
+/// would make flat tiles jump.
+fn contrast_limited(hist: &[u32; 256]) -> [u8; 256] {
+    let total = hist.iter().sum::<u32>().max(1);
+    let mut seen = 0;
+    std::array::from_fn(|v| {
+        seen += hist[v];
+        (seen as f64 * 255.0 / total as f64).round() as u8
+    })
+}
@@ -0,0 +1,114 @@
+use super::cells::average_luma;
+use super::{RenderMode, RenderedFrame, Renderer, adjust, glyph};
+use crate::options::RenderOptions;
+use image::DynamicImage;
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// Characters from the emptiest to the densest.
+const RAMP: &str = " .:-=+*#%@";
+
+/// The characters a brightness ramp is drawn with, from the emptiest to the
+/// densest. Any number of characters works, including multi-byte and wide
+/// ones; every cell takes up as many columns as the widest of them.
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Charset {
+    glyphs: Vec<String>,
+    width: usize,
+}
+
+impl Charset {
+    /// Terminal columns every character of the ramp is padded to.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The character for a brightness `level` between 0 and 255.
+    fn glyph(&self, level: u8) -> &str {
+        &self.glyphs[level as usize * self.glyphs.len() / 256]
+    }
+}
+
+impl Default for Charset {
+    fn default() -> Self {
+        RAMP.parse().expect("the built-in ramp is valid")
+    }
+}
+
+impl FromStr for Charset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let glyphs = glyph::split(s);
+        if glyphs.is_empty() {
+            return Err("the charset needs at least one character".into());
+        }
+        let width = glyphs
+            .iter()
+            .map(|g| glyph::width(g))
+            .max()
+            .unwrap_or(1)
+            .max(1);
+        let glyphs = glyphs
+            .into_iter()
+            .map(|g| {
+                let pad = width - glyph::width(&g);
+                g + &" ".repeat(pad)
+            })
+            .collect();
+        Ok(Self { glyphs, width })
+    }
+}
+
+impl TryFrom<String> for Charset {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Debug for Charset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.glyphs.concat())
+    }
+}
+
+/// Turns the image into lines of text for terminals and fonts without
+/// braille. Each character stands for the mean brightness of the pixels it
+/// covers, mapped onto a ramp of increasingly dense characters.
+pub struct Ascii;
+
+impl Renderer for Ascii {
+    fn render(&self, img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+        let mut gray = adjust::grayscale(img, opts.grayscale);
+        adjust::luma(&mut gray, opts);
+        let charset = &opts.charset;
+
+        let (w, h) = gray.dimensions();
+        let (cell_w, cell_h) = RenderMode::Ascii.cell_size();
+        // Wide characters cover as many cells' worth of pixels as they take up
+        // columns, so the output keeps the width the image was fitted to.
+        let cell_w = cell_w * charset.width() as u32;
+        let mut out =
+            String::with_capacity((w / cell_w + 2) as usize * (h / cell_h + 1) as usize * 4);
+        for y in (0..h).step_by(cell_h as usize) {
+            for x in (0..w).step_by(cell_w as usize) {
+                // Fully transparent cells stay empty.
+                let Some(mut level) = average_luma(&gray, x, y, cell_w, cell_h) else {
+                    out.push_str(&" ".repeat(charset.width()));
+                    continue;
+                };
+                if opts.invert {
+                    level = 255 - level;
+                }
+                out.push_str(charset.glyph(level));
+            }
+            out.push('\n');
+        }
+
+        RenderedFrame::from(out)
+    }
+}
@@ -0,0 +1,33 @@
+use super::cells::{Painter, pixel};
+use super::{RenderedFrame, Renderer};
+use crate::options::RenderOptions;
+use image::{DynamicImage, imageops};
+
+/// Turns the image into lines of `▀` characters with 24-bit colors: the
+/// foreground paints the upper pixel of each cell and the background the
+/// lower one.
+pub struct Blocks;
+
+impl Renderer for Blocks {
+    fn render(&self, img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+        let mut rgba = img.to_rgba8();
+        if opts.invert {
+            imageops::invert(&mut rgba);
+        }
+
+        let (w, h) = rgba.dimensions();
+        let mut out = Painter::new((w as usize * 40 + 5) * (h as usize / 2 + 1), opts);
+        for y in (0..h).step_by(2) {
+            for x in 0..w {
+                match (pixel(&rgba, x, y), pixel(&rgba, x, y + 1)) {
+                    (Some(top), bottom) => out.cell('▀', Some(top), bottom),
+                    (None, Some(bottom)) => out.cell('▄', Some(bottom), None),
+                    (None, None) => out.cell(' ', None, None),
+                }
+            }
+            out.end_line();
+        }
+
+        RenderedFrame::from(out.finish())
+    }
+}
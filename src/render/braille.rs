@@ -0,0 +1,165 @@
+use super::adjust;
+use super::cells::{ALPHA_CUTOFF, Painter, Rgb};
+use super::dither::{self, Dither};
+use super::edges::{self, Edges};
+use super::threshold::{self, ThresholdMode};
+use super::{RenderedFrame, Renderer};
+use crate::options::RenderOptions;
+use image::LumaA;
+use image::{DynamicImage, GrayAlphaImage, RgbImage};
+
+/// Transparent pixels are never lit, whether inverted or not.
+#[inline]
+fn bit_if_on(img: &GrayAlphaImage, x: u32, y: u32, t: u8, invert: bool) -> u8 {
+    if x >= img.width() || y >= img.height() {
+        return 0;
+    }
+    let LumaA([v, a]) = *img.get_pixel(x, y);
+    if a < ALPHA_CUTOFF {
+        return 0;
+    }
+    let on = if invert { v < t } else { v >= t };
+    if on { 1 } else { 0 }
+}
+
+/// Whether all pixels of the cell at `(x, y)` are transparent, so that it
+/// is left blank instead of drawn as an empty braille pattern.
+fn transparent(img: &GrayAlphaImage, x: u32, y: u32) -> bool {
+    DOTS.iter().all(|(dx, dy)| {
+        img.get_pixel_checked(x + dx, y + dy)
+            .is_none_or(|p| p[1] < ALPHA_CUTOFF)
+    })
+}
+
+/// Offsets of the dots within a cell, indexed by their bit in the braille
+/// code point.
+const DOTS: [(u32, u32); 8] = [
+    (0, 0),
+    (0, 1),
+    (0, 2),
+    (1, 0),
+    (1, 1),
+    (1, 2),
+    (0, 3),
+    (1, 3),
+];
+
+/// Average color of the dots of the cell at `(x, y)` that are set in `bits`.
+fn dot_color(rgb: &RgbImage, x: u32, y: u32, bits: u8) -> Option<Rgb> {
+    let mut sum = [0u32; 3];
+    let mut count = 0;
+    for (i, (dx, dy)) in DOTS.iter().enumerate() {
+        if bits & 1 << i == 0 {
+            continue;
+        }
+        let Some(p) = rgb.get_pixel_checked(x + dx, y + dy) else {
+            continue;
+        };
+        for (s, v) in sum.iter_mut().zip(p.0) {
+            *s += v as u32;
+        }
+        count += 1;
+    }
+    (count > 0).then(|| sum.map(|s| (s / count) as u8))
+}
+
+/// Turns the image into lines of braille characters, each terminated by a
+/// newline. Every character covers 2x4 pixels. With `color`, each character
+/// is drawn in the average 24-bit color of its lit dots.
+pub struct Braille {
+    pub color: bool,
+}
+
+impl Renderer for Braille {
+    fn render(&self, img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+        let rgb = self.color.then(|| img.to_rgb8());
+        // The pixels of a bilevel image become the dots as they are, unless
+        // they were asked to be adjusted or thresholded.
+        let direct = opts.bilevel && untouched(opts);
+        // The lines are what is drawn, whatever the background.
+        let invert = opts.invert && opts.edges.is_none() && !opts.sketch;
+        let gray = if direct {
+            log::debug!("drawing bilevel pixels as dots");
+            img.to_luma_alpha8()
+        } else {
+            binarize(img, invert, opts)
+        };
+        // Every pixel is either 0 or 255 from here on.
+        let t = 128;
+
+        let (w, h) = gray.dimensions();
+        let per_cell = if self.color { 24 } else { 3 };
+        let mut out = Painter::new((w as usize / 2 + 1) * (h as usize / 4 + 1) * per_cell, opts);
+        for y in (0..h).step_by(4) {
+            for x in (0..w).step_by(2) {
+                if transparent(&gray, x, y) {
+                    out.cell(' ', None, None);
+                    continue;
+                }
+                let mut bits: u8 = 0;
+
+                bits |= bit_if_on(&gray, x, y, t, invert);
+                bits |= bit_if_on(&gray, x, y + 1, t, invert) << 1;
+                bits |= bit_if_on(&gray, x, y + 2, t, invert) << 2;
+                bits |= bit_if_on(&gray, x + 1, y, t, invert) << 3;
+                bits |= bit_if_on(&gray, x + 1, y + 1, t, invert) << 4;
+                bits |= bit_if_on(&gray, x + 1, y + 2, t, invert) << 5;
+                bits |= bit_if_on(&gray, x, y + 3, t, invert) << 6;
+                bits |= bit_if_on(&gray, x + 1, y + 3, t, invert) << 7;
+
+                let ch = char::from_u32(0x2800 + bits as u32).unwrap_or('\u{2800}');
+                let fg = rgb.as_ref().and_then(|rgb| dot_color(rgb, x, y, bits));
+                out.cell(ch, fg, None);
+            }
+            out.end_line();
+        }
+
+        RenderedFrame::from(out.finish())
+    }
+}
+
+/// Whether none of the options that change brightness, pick the threshold or
+/// draw edges and line art are set.
+fn untouched(opts: &RenderOptions) -> bool {
+    opts.threshold.is_none()
+        && opts.threshold_percentile.is_none()
+        && opts.threshold_mode == ThresholdMode::default()
+        && opts.hysteresis.is_none()
+        && opts.dither == Dither::default()
+        && opts.levels.is_none()
+        && opts.brightness == 0
+        && opts.contrast == 0
+        && opts.posterize.is_none()
+        && opts.denoise.is_none()
+        && opts.autocontrast.is_none()
+        && !opts.equalize
+        && opts.clahe.is_none()
+        && opts.gamma == 1.0
+        && opts.emboss.is_none()
+        && !opts.sketch
+        && opts.edges.is_none()
+}
+
+/// Turns the image into dots: brightness, adjustments, edges and line art,
+/// then the threshold or dithering. `invert` is only needed by hysteresis
+/// thresholding.
+fn binarize(img: &DynamicImage, invert: bool, opts: &RenderOptions) -> GrayAlphaImage {
+    let mut gray = adjust::grayscale(img, opts.grayscale);
+    adjust::luma(&mut gray, opts);
+    if let Some(angle) = opts.emboss {
+        edges::emboss(&mut gray, angle);
+    }
+    if opts.sketch {
+        edges::sketch(&mut gray, opts.sketch_threshold, opts.sketch_sharpness);
+    }
+    edges::detect(&mut gray, opts);
+    if opts.edges.is_some_and(Edges::binary) {
+        // Already 0 or 255.
+    } else if let Some(cutoffs) = opts.hysteresis {
+        threshold::hysteresis(&mut gray, cutoffs, invert);
+    } else {
+        let t = threshold::threshold(&gray, opts);
+        dither::binarize(&mut gray, &t, opts);
+    }
+    gray
+}
@@ -0,0 +1,173 @@
+use super::color::{Color, ColorSpace};
+use crate::options::RenderOptions;
+use image::{GrayAlphaImage, LumaA, RgbaImage};
+
+pub type Rgb = [u8; 3];
+
+/// Pixels at least this opaque are drawn, the rest show the terminal
+/// background.
+pub const ALPHA_CUTOFF: u8 = 128;
+
+/// The color of the pixel at `(x, y)`, or `None` when it is transparent or
+/// lies outside the image.
+pub fn pixel(img: &RgbaImage, x: u32, y: u32) -> Option<Rgb> {
+    let [r, g, b, a] = img.get_pixel_checked(x, y)?.0;
+    (a >= ALPHA_CUTOFF).then_some([r, g, b])
+}
+
+/// The colors of the `cols`x`rows` block of pixels whose top left corner is
+/// `(x, y)`, row by row, or `None` when all of them are transparent.
+/// Transparent pixels take the color of the first opaque one and pixels past
+/// the edge repeat the last row or column.
+pub fn block(img: &RgbaImage, x: u32, y: u32, cols: u32, rows: u32) -> Option<Vec<Rgb>> {
+    let (w, h) = img.dimensions();
+    let colors: Vec<Option<Rgb>> = (0..rows)
+        .flat_map(|dy| (0..cols).map(move |dx| (dx, dy)))
+        .map(|(dx, dy)| pixel(img, (x + dx).min(w - 1), (y + dy).min(h - 1)))
+        .collect();
+    let fill = colors.iter().flatten().next().copied()?;
+    Some(colors.into_iter().map(|c| c.unwrap_or(fill)).collect())
+}
+
+/// The mean brightness of the opaque pixels of the `cols`x`rows` block whose
+/// top left corner is `(x, y)`, cut off at the edges of the image, or `None`
+/// when all of them are transparent.
+pub fn average_luma(gray: &GrayAlphaImage, x: u32, y: u32, cols: u32, rows: u32) -> Option<u8> {
+    let (w, h) = gray.dimensions();
+    let mut sum = 0u32;
+    let mut count = 0u32;
+    for py in y..(y + rows).min(h) {
+        for px in x..(x + cols).min(w) {
+            let LumaA([v, a]) = *gray.get_pixel(px, py);
+            if a >= ALPHA_CUTOFF {
+                sum += v as u32;
+                count += 1;
+            }
+        }
+    }
+    sum.checked_div(count).map(|mean| mean as u8)
+}
+
+/// Builds colored output one character cell at a time. Escape sequences are
+/// only emitted when a color changes and lines that end in color end with a
+/// reset, so cells without colors come out as plain text.
+pub struct Painter {
+    out: String,
+    colors: ColorSpace,
+    fg: Option<Color>,
+    bg: Option<Color>,
+}
+
+impl Painter {
+    pub fn new(capacity: usize, opts: &RenderOptions) -> Self {
+        Self::with_colors(capacity, ColorSpace::new(opts))
+    }
+
+    /// A painter limited to `colors` whatever the options say.
+    pub fn with_colors(capacity: usize, colors: ColorSpace) -> Self {
+        Self {
+            out: String::with_capacity(capacity),
+            colors,
+            fg: None,
+            bg: None,
+        }
+    }
+
+    /// Draws `ch` in `fg` on `bg`, or rather in the closest colors the
+    /// terminal can show; `None` leaves the terminal's default.
+    pub fn cell(&mut self, ch: char, fg: Option<Rgb>, bg: Option<Rgb>) {
+        // The foreground of a blank cell is invisible, keep whatever is set.
+        let fg = if matches!(ch, ' ' | '\u{2800}') {
+            self.fg
+        } else {
+            fg.map(|fg| self.colors.color(fg))
+        };
+        let bg = bg.map(|bg| self.colors.color(bg));
+        if fg != self.fg {
+            match fg {
+                Some(fg) => fg.write_sgr(&mut self.out, 38),
+                None => self.out.push_str("\x1b[39m"),
+            }
+            self.fg = fg;
+        }
+        if bg != self.bg {
+            match bg {
+                Some(bg) => bg.write_sgr(&mut self.out, 48),
+                None => self.out.push_str("\x1b[49m"),
+            }
+            self.bg = bg;
+        }
+        self.out.push(ch);
+    }
+
+    pub fn end_line(&mut self) {
+        if self.fg.is_some() || self.bg.is_some() {
+            self.out.push_str("\x1b[0m");
+        }
+        self.out.push('\n');
+        self.fg = None;
+        self.bg = None;
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+/// Splits the pixels of a cell into two groups so that painting each group
+/// in its mean color is as close to the original as possible. Returns the
+/// mask of the pixels in the foreground group (bit `i` for `pixels[i]`)
+/// with the foreground and background colors. The last pixel always ends up
+/// in the background, so only half of the masks need to be tried.
+pub fn two_color_split(pixels: &[Rgb]) -> (u32, Rgb, Rgb) {
+    let masks = 0..1u32 << (pixels.len() - 1);
+    let (_, mask, fg, bg) = masks
+        .map(|mask| {
+            let (err, fg, bg) = fit_mask(pixels, mask);
+            (err, mask, fg, bg)
+        })
+        .min_by_key(|&(err, ..)| err)
+        .expect("there is always at least one mask");
+    (mask, fg, bg)
+}
+
+/// Paints the pixels in `mask` in their mean color and the others in
+/// theirs. Returns the squared error of doing so together with the
+/// foreground and background colors.
+pub fn fit_mask(pixels: &[Rgb], mask: u32) -> (u64, Rgb, Rgb) {
+    // One pass over the pixels is enough: the error of painting a group in
+    // its mean is the sum of its squares minus count times the squared mean.
+    let mut on = [0u64; 3];
+    let mut off = [0u64; 3];
+    let mut squares = 0u64;
+    let mut on_count = 0u64;
+    for (i, p) in pixels.iter().enumerate() {
+        let sums = if mask & 1 << i != 0 {
+            on_count += 1;
+            &mut on
+        } else {
+            &mut off
+        };
+        for (s, &v) in sums.iter_mut().zip(p) {
+            *s += v as u64;
+            squares += v as u64 * v as u64;
+        }
+    }
+    let off_count = pixels.len() as u64 - on_count;
+
+    let explained = |sums: &[u64; 3], count: u64| -> u64 {
+        if count == 0 {
+            return 0;
+        }
+        sums.iter().map(|&s| s * s / count).sum()
+    };
+    let err = squares - explained(&on, on_count) - explained(&off, off_count);
+    (err, mean_of(&on, on_count), mean_of(&off, off_count))
+}
+
+fn mean_of(sums: &[u64; 3], count: u64) -> Rgb {
+    if count == 0 {
+        return [0; 3];
+    }
+    sums.map(|s| ((s + count / 2) / count) as u8)
+}
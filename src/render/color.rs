@@ -0,0 +1,389 @@
+use super::cells::Rgb;
+use crate::options::RenderOptions;
+use clap::ValueEnum;
+use image::{DynamicImage, RgbaImage};
+use serde::Deserialize;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// How many colors the terminal can show.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(try_from = "DepthValue")]
+pub enum ColorDepth {
+    /// 24-bit colors
+    #[default]
+    #[value(name = "truecolor")]
+    Truecolor,
+    /// The xterm 256 color palette
+    #[value(name = "256")]
+    Ansi256,
+    /// The 16 standard ANSI colors (see --ansi-colors)
+    #[value(name = "16")]
+    Ansi16,
+}
+
+/// `colors = 256` and `colors = "256"` both work in the config file.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DepthValue {
+    Number(u32),
+    Name(String),
+}
+
+impl TryFrom<DepthValue> for ColorDepth {
+    type Error = String;
+
+    fn try_from(value: DepthValue) -> Result<Self, Self::Error> {
+        let name = match value {
+            DepthValue::Number(n) => n.to_string(),
+            DepthValue::Name(name) => name,
+        };
+        ColorDepth::from_str(&name, true)
+    }
+}
+
+/// What the 16 standard ANSI colors look like, in the order black, red,
+/// green, yellow, blue, magenta, cyan, white and then their bright
+/// variants. Themes redefine them, so the mapping can be configured; the
+/// default is xterm's.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "PaletteValue")]
+pub struct AnsiPalette(pub [Rgb; 16]);
+
+impl Default for AnsiPalette {
+    fn default() -> Self {
+        AnsiPalette([
+            [0x00, 0x00, 0x00],
+            [0xcd, 0x00, 0x00],
+            [0x00, 0xcd, 0x00],
+            [0xcd, 0xcd, 0x00],
+            [0x00, 0x00, 0xee],
+            [0xcd, 0x00, 0xcd],
+            [0x00, 0xcd, 0xcd],
+            [0xe5, 0xe5, 0xe5],
+            [0x7f, 0x7f, 0x7f],
+            [0xff, 0x00, 0x00],
+            [0x00, 0xff, 0x00],
+            [0xff, 0xff, 0x00],
+            [0x5c, 0x5c, 0xff],
+            [0xff, 0x00, 0xff],
+            [0x00, 0xff, 0xff],
+            [0xff, 0xff, 0xff],
+        ])
+    }
+}
+
+impl FromStr for AnsiPalette {
+    type Err = String;
+
+    /// Parses 16 hex colors separated by commas or spaces.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let colors = s
+            .split([',', ' '])
+            .filter(|c| !c.is_empty())
+            .map(parse_hex)
+            .collect::<Result<Vec<Rgb>, String>>()?;
+        let count = colors.len();
+        let colors: [Rgb; 16] = colors
+            .try_into()
+            .map_err(|_| format!("expected 16 colors, got {count}"))?;
+        Ok(AnsiPalette(colors))
+    }
+}
+
+/// The ANSI colors can be given as one string or as a list in the config
+/// file.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PaletteValue {
+    List(Vec<String>),
+    Text(String),
+}
+
+impl TryFrom<PaletteValue> for AnsiPalette {
+    type Error = String;
+
+    fn try_from(value: PaletteValue) -> Result<Self, Self::Error> {
+        match value {
+            PaletteValue::List(list) => list.join(",").parse(),
+            PaletteValue::Text(text) => text.parse(),
+        }
+    }
+}
+
+/// Parses `#rrggbb` or `rrggbb`.
+pub fn parse_hex(s: &str) -> Result<Rgb, String> {
+    let hex = s.trim().trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+    };
+    match (hex.len(), channel(0), channel(2), channel(4)) {
+        (6, Some(r), Some(g), Some(b)) => Ok([r, g, b]),
+        _ => Err(format!("`{s}` is not a #rrggbb color")),
+    }
+}
+
+/// What transparent pixels are drawn against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Background {
+    /// Leave them transparent so the terminal background shows through.
+    #[default]
+    Transparent,
+    /// Blend them onto this color.
+    Color(Rgb),
+}
+
+impl FromStr for Background {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "transparent" => Ok(Background::Transparent),
+            "black" => Ok(Background::Color([0, 0, 0])),
+            "white" => Ok(Background::Color([255, 255, 255])),
+            s => parse_hex(s)
+                .map(Background::Color)
+                .map_err(|_| format!("`{s}` is neither `transparent` nor a #rrggbb color")),
+        }
+    }
+}
+
+impl TryFrom<String> for Background {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Blends every pixel onto `bg` according to its alpha, leaving an opaque
+/// image.
+pub fn flatten(img: DynamicImage, bg: Rgb) -> DynamicImage {
+    let mut rgba = img.to_rgba8();
+    for pixel in rgba.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        let blend = |c: u8, bg: u8| {
+            ((c as u32 * a as u32 + bg as u32 * (255 - a as u32) + 127) / 255) as u8
+        };
+        pixel.0 = [blend(r, bg[0]), blend(g, bg[1]), blend(b, bg[2]), 255];
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// Blends every pixel onto a checkerboard of white and light gray squares of
+/// `size` pixels, the way image editors show transparency.
+pub fn checker(img: DynamicImage, size: u32) -> DynamicImage {
+    let size = size.max(1);
+    let mut rgba = img.to_rgba8();
+    for (x, y, pixel) in rgba.enumerate_pixels_mut() {
+        let bg = if (x / size + y / size).is_multiple_of(2) {
+            255
+        } else {
+            204
+        };
+        let [r, g, b, a] = pixel.0;
+        let blend = |c: u8| ((c as u32 * a as u32 + bg * (255 - a as u32) + 127) / 255) as u8;
+        pixel.0 = [blend(r), blend(g), blend(b), 255];
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
+
+/// A color as it is sent to the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Rgb(Rgb),
+    Indexed(u8),
+    /// One of the 16 standard colors, selected with the oldest SGR codes.
+    Ansi(u8),
+}
+
+impl Color {
+    /// Appends the SGR parameters selecting this color, `base` being 38 for
+    /// the foreground and 48 for the background.
+    pub fn write_sgr(self, out: &mut String, base: u8) {
+        let _ = match self {
+            Color::Rgb([r, g, b]) => write!(out, "\x1b[{base};2;{r};{g};{b}m"),
+            Color::Indexed(n) => write!(out, "\x1b[{base};5;{n}m"),
+            // 30-37 and 40-47, the bright colors 60 above them.
+            Color::Ansi(n) if n < 8 => write!(out, "\x1b[{}m", base - 8 + n),
+            Color::Ansi(n) => write!(out, "\x1b[{}m", base - 8 + 60 + n - 8),
+        };
+    }
+}
+
+/// Maps the colors the renderers want onto the ones the output may use:
+/// first onto the palette if one was chosen, then onto what the terminal
+/// can show.
+#[derive(Debug, Clone)]
+pub struct ColorSpace {
+    palette: Option<Vec<Rgb>>,
+    depth: Depth,
+}
+
+#[derive(Debug, Clone)]
+enum Depth {
+    Truecolor,
+    Xterm256,
+    Ansi16(AnsiPalette),
+    /// Only the 24 steps of the xterm gray ramp.
+    Grays,
+}
+
+impl ColorSpace {
+    pub fn new(opts: &RenderOptions) -> Self {
+        let depth = match opts.colors {
+            ColorDepth::Truecolor => Depth::Truecolor,
+            ColorDepth::Ansi256 => Depth::Xterm256,
+            ColorDepth::Ansi16 => Depth::Ansi16(opts.ansi_colors.clone()),
+        };
+        Self {
+            palette: opts.palette.clone(),
+            depth,
+        }
+    }
+
+    /// The gray ramp of the xterm palette, colors 232 to 255.
+    pub fn grays() -> Self {
+        Self {
+            palette: None,
+            depth: Depth::Grays,
+        }
+    }
+
+    /// Whether every color can be shown as it is.
+    fn is_exact(&self) -> bool {
+        self.palette.is_none() && matches!(self.depth, Depth::Truecolor)
+    }
+
+    /// The closest color the terminal can show.
+    pub fn color(&self, rgb: Rgb) -> Color {
+        let rgb = self.snap(rgb);
+        match &self.depth {
+            Depth::Truecolor => Color::Rgb(rgb),
+            Depth::Xterm256 => Color::Indexed(nearest_xterm(rgb)),
+            Depth::Ansi16(ansi) => Color::Ansi(nearest(&ansi.0, rgb) as u8),
+            Depth::Grays => Color::Indexed(nearest_gray(rgb)),
+        }
+    }
+
+    /// What the terminal actually shows for `rgb`.
+    fn shown(&self, rgb: Rgb) -> Rgb {
+        let rgb = self.snap(rgb);
+        match &self.depth {
+            Depth::Truecolor => rgb,
+            Depth::Xterm256 => xterm_rgb(nearest_xterm(rgb)),
+            Depth::Ansi16(ansi) => ansi.0[nearest(&ansi.0, rgb)],
+            Depth::Grays => xterm_rgb(nearest_gray(rgb)),
+        }
+    }
+
+    /// The palette color closest to `rgb`.
+    fn snap(&self, rgb: Rgb) -> Rgb {
+        match &self.palette {
+            Some(palette) => palette[nearest(palette, rgb)],
+            None => rgb,
+        }
+    }
+}
+
+/// Index of the entry of `palette` closest to `rgb`.
+fn nearest(palette: &[Rgb], rgb: Rgb) -> usize {
+    (0..palette.len())
+        .min_by_key(|&i| distance(palette[i], rgb))
+        .unwrap_or(0)
+}
+
+/// Intensities of the six steps of the xterm 6x6x6 color cube.
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// The xterm palette entry closest to `rgb`, out of the color cube
+/// (16-231) and the gray ramp (232-255). The 16 system colors are left out
+/// because every theme redefines them.
+fn nearest_xterm([r, g, b]: Rgb) -> u8 {
+    let step = |v: u8| {
+        (0..CUBE.len())
+            .min_by_key(|&i| (CUBE[i] as i32 - v as i32).abs())
+            .unwrap_or(0) as u8
+    };
+    let cube = 16 + 36 * step(r) + 6 * step(g) + step(b);
+
+    let gray = nearest_gray([r, g, b]);
+    if distance([r, g, b], xterm_rgb(gray)) < distance([r, g, b], xterm_rgb(cube)) {
+        gray
+    } else {
+        cube
+    }
+}
+
+/// The step of the xterm gray ramp (232-255) closest to the brightness of
+/// `rgb`.
+fn nearest_gray([r, g, b]: Rgb) -> u8 {
+    let mean = (r as u32 + g as u32 + b as u32) / 3;
+    232 + (mean.saturating_sub(3) / 10).min(23) as u8
+}
+
+/// The color of xterm palette entry `n`, for `n` of 16 and above.
+fn xterm_rgb(n: u8) -> Rgb {
+    if n >= 232 {
+        let v = 8 + 10 * (n - 232);
+        return [v, v, v];
+    }
+    let i = n.saturating_sub(16);
+    [
+        CUBE[(i / 36) as usize],
+        CUBE[(i / 6 % 6) as usize],
+        CUBE[(i % 6) as usize],
+    ]
+}
+
+fn distance(a: Rgb, b: Rgb) -> u32 {
+    a.iter()
+        .zip(b)
+        .map(|(&a, b)| (a as i32 - b as i32).pow(2) as u32)
+        .sum()
+}
+
+/// Floyd-Steinberg dithers the image to the colors of `colors`, so
+/// gradients survive a small palette as patterns instead of bands.
+/// With `opts.serpentine`, every other row is scanned from right to left,
+/// and only `opts.dither_strength` of the error is passed on.
+pub fn dither(img: DynamicImage, colors: &ColorSpace, opts: &RenderOptions) -> DynamicImage {
+    if colors.is_exact() {
+        return img;
+    }
+    let mut rgba: RgbaImage = img.to_rgba8();
+    let (w, h) = rgba.dimensions();
+    let mut errors = vec![[0i32; 3]; (w as usize + 2) * 2];
+    let row_len = w as usize + 2;
+    for y in 0..h {
+        let (current, next) = errors.split_at_mut(row_len);
+        next.fill([0; 3]);
+        let reverse = opts.serpentine && y % 2 == 1;
+        for step in 0..w {
+            let x = if reverse { w - 1 - step } else { step };
+            let i = x as usize + 1;
+            let (ahead, behind) = if reverse {
+                (i - 1, i + 1)
+            } else {
+                (i + 1, i - 1)
+            };
+            let pixel = rgba.get_pixel_mut(x, y);
+            let wanted: Rgb =
+                std::array::from_fn(|c| (pixel[c] as i32 + current[i][c] / 16).clamp(0, 255) as u8);
+            let shown = colors.shown(wanted);
+            pixel.0[..3].copy_from_slice(&shown);
+            for c in 0..3 {
+                let err =
+                    ((wanted[c] as i32 - shown[c] as i32) as f32 * opts.dither_strength) as i32;
+                current[ahead][c] += err * 7;
+                next[behind][c] += err * 3;
+                next[i][c] += err * 5;
+                next[ahead][c] += err;
+            }
+        }
+        errors.rotate_left(row_len);
+    }
+    DynamicImage::ImageRgba8(rgba)
+}
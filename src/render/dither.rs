@@ -0,0 +1,163 @@
+use super::cells::ALPHA_CUTOFF;
+use super::threshold::Threshold;
+use crate::options::RenderOptions;
+use clap::ValueEnum;
+use image::GrayAlphaImage;
+use serde::Deserialize;
+
+/// How the brightness of the pixels is turned into dots that are on or off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dither {
+    /// Compare every pixel to the threshold
+    #[default]
+    None,
+    /// Floyd-Steinberg error diffusion, keeps gradients as dot patterns
+    FloydSteinberg,
+    /// Atkinson error diffusion, brighter and punchier as it only passes on
+    /// 3/4 of the error
+    Atkinson,
+    /// Ordered dithering with a tiled Bayer matrix (see --bayer-size), stable
+    /// across animation frames
+    Bayer,
+    /// Ordered dithering with a blue noise texture, without the cross-hatch
+    /// of Bayer matrices or the worms of error diffusion
+    BlueNoise,
+    /// Random noise added to the threshold (see --seed)
+    Random,
+}
+
+/// Where the error of a pixel goes: `(dx, dy, weight)`, each neighbour
+/// getting `weight / divisor` of it.
+struct Kernel {
+    divisor: f32,
+    taps: &'static [(i32, u32, f32)],
+}
+
+const FLOYD_STEINBERG: Kernel = Kernel {
+    divisor: 16.0,
+    taps: &[(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)],
+};
+
+/// Only 6/8 of the error is passed on, the rest is dropped.
+const ATKINSON: Kernel = Kernel {
+    divisor: 8.0,
+    taps: &[
+        (1, 0, 1.0),
+        (2, 0, 1.0),
+        (-1, 1, 1.0),
+        (0, 1, 1.0),
+        (1, 1, 1.0),
+        (0, 2, 1.0),
+    ],
+};
+
+/// A 64x64 blue noise threshold texture, one byte per entry, made with the
+/// void-and-cluster method (Gaussian of sigma 1.5). Tiles without seams.
+const BLUE_NOISE: &[u8; 64 * 64] = include_bytes!("blue_noise.bin");
+
+/// Sets the brightness of every opaque pixel to 0 or 255, depending on how it
+/// compares to its threshold and on the dithering method.
+pub fn binarize(gray: &mut GrayAlphaImage, t: &Threshold, opts: &RenderOptions) {
+    match opts.dither {
+        Dither::None => {
+            for (x, y, pixel) in gray.enumerate_pixels_mut() {
+                pixel[0] = if pixel[0] >= t.at(x, y) { 255 } else { 0 };
+            }
+        }
+        Dither::FloydSteinberg => diffuse(gray, t, &FLOYD_STEINBERG, opts),
+        Dither::Atkinson => diffuse(gray, t, &ATKINSON, opts),
+        Dither::Bayer => {
+            let size = opts.bayer_size.next_power_of_two().clamp(2, 16);
+            let matrix: Vec<f32> = (0..size * size)
+                .map(|i| (bayer(i % size, i / size, size) as f32 + 0.5) / (size * size) as f32)
+                .collect();
+            ordered(gray, t, opts.dither_strength, |x, y| {
+                matrix[((y % size) * size + x % size) as usize]
+            });
+        }
+        Dither::BlueNoise => ordered(gray, t, opts.dither_strength, |x, y| {
+            (BLUE_NOISE[((y % 64) * 64 + x % 64) as usize] as f32 + 0.5) / 256.0
+        }),
+        Dither::Random => ordered(gray, t, opts.dither_strength, |x, y| noise(opts.seed, x, y)),
+    }
+}
+
+/// Uniform noise between 0 and 1 that only depends on the seed and the
+/// position, so the same seed always gives the same output.
+fn noise(seed: u64, x: u32, y: u32) -> f32 {
+    // SplitMix64's finalizer, a cheap and well mixing hash.
+    let mut z = seed ^ ((y as u64) << 32 | x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^= z >> 31;
+    (z >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Ordered dithering: a pixel lights up when its brightness is at least
+/// `level(x, y)`, a value between 0 and 1 that `strength` pulls towards the
+/// middle. Brightness is scaled so that `t` sits in the middle of that
+/// range, black never lights up and white always does.
+fn ordered(
+    gray: &mut GrayAlphaImage,
+    t: &Threshold,
+    strength: f32,
+    level: impl Fn(u32, u32) -> f32,
+) {
+    for (x, y, pixel) in gray.enumerate_pixels_mut() {
+        let t = t.at(x, y) as f32;
+        let v = pixel[0] as f32;
+        let relative = if v < t {
+            v / t * 0.5
+        } else {
+            0.5 + (v - t) / (255.0 - t).max(1.0) * 0.5
+        };
+        let level = 0.5 + (level(x, y) - 0.5) * strength;
+        pixel[0] = if relative >= level { 255 } else { 0 };
+    }
+}
+
+/// Entry `(x, y)` of the `size`x`size` Bayer matrix, built by recursively
+/// splitting it into quadrants that take the values in the order 0, 3, 1, 2
+/// (top left, bottom right, top right, bottom left).
+fn bayer(x: u32, y: u32, size: u32) -> u32 {
+    if size == 1 {
+        return 0;
+    }
+    let half = size / 2;
+    let quadrant = [[0, 2], [3, 1]][(y / half) as usize][(x / half) as usize];
+    4 * bayer(x % half, y % half, half) + quadrant
+}
+
+/// Error diffusion: every pixel is set to 0 or 255 and the difference to its
+/// actual brightness is spread over the neighbours not yet visited.
+/// Transparent pixels neither take nor pass on any error, and only
+/// `opts.dither_strength` of it is passed on at all. With `opts.serpentine`,
+/// every other row is scanned from right to left, with the kernel mirrored,
+/// which keeps the error from drifting to one side.
+fn diffuse(gray: &mut GrayAlphaImage, t: &Threshold, kernel: &Kernel, opts: &RenderOptions) {
+    let (w, h) = gray.dimensions();
+    let mut values: Vec<f32> = gray.pixels().map(|p| p[0] as f32).collect();
+    for y in 0..h {
+        let reverse = opts.serpentine && y % 2 == 1;
+        for step in 0..w {
+            let x = if reverse { w - 1 - step } else { step };
+            let i = (y * w + x) as usize;
+            if gray.get_pixel(x, y)[1] < ALPHA_CUTOFF {
+                continue;
+            }
+            let v = values[i];
+            let on = v >= t.at(x, y) as f32;
+            let err = (v - if on { 255.0 } else { 0.0 }) * opts.dither_strength;
+            gray.get_pixel_mut(x, y)[0] = if on { 255 } else { 0 };
+            for &(dx, dy, weight) in kernel.taps {
+                let dx = if reverse { -dx } else { dx };
+                let (nx, ny) = (x as i32 + dx, y + dy);
+                if nx < 0 || nx >= w as i32 || ny >= h {
+                    continue;
+                }
+                values[(ny * w + nx as u32) as usize] += err * weight / kernel.divisor;
+            }
+        }
+    }
+}
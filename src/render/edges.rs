@@ -0,0 +1,208 @@
+use super::threshold;
+use crate::options::RenderOptions;
+use clap::ValueEnum;
+use image::GrayAlphaImage;
+use serde::Deserialize;
+
+/// How --edges finds the outlines the braille modes draw instead of the
+/// brightness.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Edges {
+    /// The strength of the Sobel gradient, thresholded like brightness
+    Sobel,
+    /// Canny's detector, one dot wide outlines kept by hysteresis (see
+    /// --edge-lo and --edge-hi), for diagrams and screenshots
+    Canny,
+}
+
+impl Edges {
+    /// Whether the detector already decides which dots are lit, so the
+    /// threshold and dithering do not apply.
+    pub fn binary(self) -> bool {
+        self == Edges::Canny
+    }
+}
+
+/// Replaces the brightness with a relief lit from `angle` degrees (0 is from
+/// the right, counterclockwise, so 135 is from the top left): gray where the
+/// image is flat, lighter on slopes facing the light and darker on those
+/// facing away, taking the brightness as height.
+pub fn emboss(gray: &mut GrayAlphaImage, angle: f32) {
+    log::info!("emboss angle={angle}");
+    let (w, h) = gray.dimensions();
+    let values: Vec<f32> = gray
+        .pixels()
+        .map(|p| p[0] as f32 * p[1] as f32 / 255.0)
+        .collect();
+    let gradient = Gradient::new(&values, w, h);
+    let (sin, cos) = angle.to_radians().sin_cos();
+    for ((pixel, gx), gy) in gray.pixels_mut().zip(gradient.dx).zip(gradient.dy) {
+        // Rows count downwards, so a light from above has a negative y.
+        let lit = -gx * cos + gy * sin;
+        pixel[0] = (128.0 + lit / 8.0).round().clamp(0.0, 255.0) as u8;
+    }
+}
+
+/// Replaces the brightness with pen-and-ink line art made with the extended
+/// difference of Gaussians (XDoG) of Winnemöller et al.: the image is
+/// sharpened by subtracting a wider blur, which overshoots below 0 on the
+/// dark side of every edge, and everything below `threshold`, between 0 and
+/// 1, becomes ink that fades out faster the higher `sharpness` is. The ink
+/// comes out bright, like edges.
+pub fn sketch(gray: &mut GrayAlphaImage, threshold: f32, sharpness: f32) {
+    log::info!("sketch threshold={threshold} sharpness={sharpness}");
+    const SIGMA: f32 = 1.0;
+    const K: f32 = 1.6;
+    const P: f32 = 20.0;
+    let (w, h) = gray.dimensions();
+    let values: Vec<f32> = gray
+        .pixels()
+        .map(|p| p[0] as f32 * p[1] as f32 / (255.0 * 255.0))
+        .collect();
+    let narrow = blur(&values, w, h, SIGMA);
+    let wide = blur(&values, w, h, SIGMA * K);
+    for ((pixel, n), w) in gray.pixels_mut().zip(narrow).zip(wide) {
+        let d = (1.0 + P) * n - P * w;
+        let paper = if d >= threshold {
+            1.0
+        } else {
+            1.0 + (sharpness * (d - threshold)).tanh()
+        };
+        pixel[0] = ((1.0 - paper) * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+}
+
+/// Replaces the brightness with the strength of the edge at every pixel, or
+/// for binary detectors with 255 on the edges and 0 elsewhere. Transparent
+/// pixels count as black, so the outline of a shape shows too.
+pub fn detect(gray: &mut GrayAlphaImage, opts: &RenderOptions) {
+    let Some(edges) = opts.edges else {
+        return;
+    };
+    log::info!("edges={edges:?}");
+    let (w, h) = gray.dimensions();
+    let values: Vec<f32> = gray
+        .pixels()
+        .map(|p| p[0] as f32 * p[1] as f32 / 255.0)
+        .collect();
+    let magnitude = match edges {
+        Edges::Sobel => Gradient::new(&values, w, h).magnitude,
+        Edges::Canny => Gradient::new(&blur(&values, w, h, 1.4), w, h).thin(),
+    };
+    for (pixel, m) in gray.pixels_mut().zip(magnitude) {
+        pixel[0] = m.round().min(255.0) as u8;
+    }
+    if edges == Edges::Canny {
+        threshold::hysteresis(gray, (opts.edge_lo, opts.edge_hi), false);
+    }
+}
+
+/// The value at `(x, y)` of a `w`x`h` image, extending its edges outwards.
+fn at(values: &[f32], w: u32, h: u32, x: i64, y: i64) -> f32 {
+    let x = x.clamp(0, w as i64 - 1);
+    let y = y.clamp(0, h as i64 - 1);
+    values[(y * w as i64 + x) as usize]
+}
+
+/// Gaussian blur of standard deviation `sigma`, done as a horizontal and a
+/// vertical pass.
+fn blur(values: &[f32], w: u32, h: u32, sigma: f32) -> Vec<f32> {
+    let radius = (sigma * 3.0).ceil() as i64;
+    let kernel: Vec<f32> = (-radius..=radius)
+        .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f32 = kernel.iter().sum();
+    let pass = |values: &[f32], (dx, dy): (i64, i64)| -> Vec<f32> {
+        let mut out = Vec::with_capacity(values.len());
+        for y in 0..h as i64 {
+            for x in 0..w as i64 {
+                let blurred: f32 = (-radius..=radius)
+                    .zip(&kernel)
+                    .map(|(i, k)| k * at(values, w, h, x + i * dx, y + i * dy))
+                    .sum();
+                out.push(blurred / sum);
+            }
+        }
+        out
+    };
+    pass(&pass(values, (1, 0)), (0, 1))
+}
+
+/// The Sobel gradient of an image.
+struct Gradient {
+    width: u32,
+    height: u32,
+    /// Strength of the gradient, scaled so that a hard edge between black
+    /// and white is about 255.
+    magnitude: Vec<f32>,
+    /// Direction of the gradient.
+    dx: Vec<f32>,
+    dy: Vec<f32>,
+}
+
+impl Gradient {
+    fn new(values: &[f32], w: u32, h: u32) -> Self {
+        let at = |x: i64, y: i64| at(values, w, h, x, y);
+        let mut gradient = Gradient {
+            width: w,
+            height: h,
+            magnitude: Vec::with_capacity(values.len()),
+            dx: Vec::with_capacity(values.len()),
+            dy: Vec::with_capacity(values.len()),
+        };
+        for y in 0..h as i64 {
+            for x in 0..w as i64 {
+                let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                    - at(x - 1, y - 1)
+                    - 2.0 * at(x - 1, y)
+                    - at(x - 1, y + 1);
+                let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                    - at(x - 1, y - 1)
+                    - 2.0 * at(x, y - 1)
+                    - at(x + 1, y - 1);
+                gradient.magnitude.push((gx * gx + gy * gy).sqrt() / 4.0);
+                gradient.dx.push(gx);
+                gradient.dy.push(gy);
+            }
+        }
+        gradient
+    }
+
+    /// Non-maximum suppression: the magnitude where it is at least as strong
+    /// as both neighbours across the edge, 0 elsewhere, which thins the edges
+    /// to one pixel.
+    fn thin(&self) -> Vec<f32> {
+        let (w, h) = (self.width, self.height);
+        let mut out = vec![0.0; self.magnitude.len()];
+        for y in 0..h as i64 {
+            for x in 0..w as i64 {
+                let i = (y * w as i64 + x) as usize;
+                let m = self.magnitude[i];
+                if m == 0.0 {
+                    continue;
+                }
+                // The gradient direction rounded to one of 4 neighbour pairs.
+                let angle = self.dy[i].atan2(self.dx[i]).to_degrees().rem_euclid(180.0);
+                let (sx, sy) = match angle {
+                    a if !(22.5..157.5).contains(&a) => (1, 0),
+                    a if a < 67.5 => (1, 1),
+                    a if a < 112.5 => (0, 1),
+                    _ => (-1, 1),
+                };
+                let neighbour = |s: i64| {
+                    let (nx, ny) = (x + s * sx, y + s * sy);
+                    if nx < 0 || ny < 0 || nx >= w as i64 || ny >= h as i64 {
+                        0.0
+                    } else {
+                        self.magnitude[(ny * w as i64 + nx) as usize]
+                    }
+                };
+                if m >= neighbour(1) && m >= neighbour(-1) {
+                    out[i] = m;
+                }
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,99 @@
+use super::cells::{Rgb, block};
+use super::{RenderMode, RenderedFrame, Renderer};
+use crate::options::RenderOptions;
+use image::{DynamicImage, imageops};
+
+/// Emoji that are mostly one color, with roughly the mean color of their
+/// glyph as common emoji fonts draw it. The colored squares come first so
+/// they win ties and keep the mosaic tidy.
+const EMOJI: &[(&str, u32)] = &[
+    ("⬛", 0x31373d),
+    ("⬜", 0xe6e7e8),
+    ("🟥", 0xdd2e44),
+    ("🟧", 0xf4900c),
+    ("🟨", 0xfdcb58),
+    ("🟩", 0x78b159),
+    ("🟦", 0x55acee),
+    ("🟪", 0xaa8ed6),
+    ("🟫", 0xc1694f),
+    ("🌑", 0x66757f),
+    ("🐘", 0x99aab5),
+    ("🧊", 0xbbddf5),
+    ("🩵", 0x88c9f9),
+    ("🫐", 0x3b5998),
+    ("🍇", 0x744eaa),
+    ("🍆", 0x553788),
+    ("🩷", 0xf7a4c0),
+    ("🐷", 0xf4abba),
+    ("🌹", 0xbe1931),
+    ("🍊", 0xf28b2c),
+    ("🍋", 0xffe567),
+    ("🍞", 0xd99e82),
+    ("🥔", 0xc9a26b),
+    ("🍫", 0x8a4b38),
+    ("🌰", 0x662113),
+    ("🌲", 0x3e721d),
+    ("🥝", 0x8aad3c),
+    ("🍈", 0xc6e5b3),
+    ("🌊", 0x3b88c3),
+    ("🦩", 0xf2a1b3),
+];
+
+fn rgb(hex: u32) -> Rgb {
+    [(hex >> 16) as u8, (hex >> 8) as u8, hex as u8]
+}
+
+/// The emoji whose color is closest to `color`.
+fn nearest(color: Rgb) -> &'static str {
+    let distance = |hex: u32| -> u32 {
+        rgb(hex)
+            .iter()
+            .zip(color)
+            .map(|(&a, b)| (a as i32 - b as i32).pow(2) as u32)
+            .sum()
+    };
+    EMOJI
+        .iter()
+        .min_by_key(|&&(_, hex)| distance(hex))
+        .map_or(" ", |&(emoji, _)| emoji)
+}
+
+/// Turns the image into a mosaic of emoji, each standing for the mean color
+/// of the pixels it covers. The output is plain text without escape
+/// sequences, so it can be pasted into chats and documents. Emoji are two
+/// columns wide, so each covers 2x2 pixels.
+pub struct Emoji;
+
+impl Renderer for Emoji {
+    fn render(&self, img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+        let mut rgba = img.to_rgba8();
+        if opts.invert {
+            imageops::invert(&mut rgba);
+        }
+
+        let (w, h) = rgba.dimensions();
+        let (cell_w, cell_h) = RenderMode::Emoji.cell_size();
+        let cell_w = cell_w * 2;
+        let mut out =
+            String::with_capacity((w / cell_w + 2) as usize * (h / cell_h + 1) as usize * 4);
+        for y in (0..h).step_by(cell_h as usize) {
+            for x in (0..w).step_by(cell_w as usize) {
+                match block(&rgba, x, y, cell_w, cell_h) {
+                    Some(pixels) => {
+                        let sums = pixels.iter().fold([0u32; 3], |mut sums, p| {
+                            for (s, &v) in sums.iter_mut().zip(p) {
+                                *s += v as u32;
+                            }
+                            sums
+                        });
+                        out.push_str(nearest(sums.map(|s| (s / pixels.len() as u32) as u8)));
+                    }
+                    None => out.push_str("  "),
+                }
+            }
+            out.push('\n');
+        }
+
+        RenderedFrame::from(out)
+    }
+}
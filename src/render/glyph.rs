@@ -0,0 +1,87 @@
+/// Splits `s` into user-perceived characters: a base character followed by
+/// the combining marks, variation selectors and zero width joiner sequences
+/// that attach to it. This covers accents and most emoji without pulling in
+/// the full Unicode segmentation tables.
+pub fn split(s: &str) -> Vec<String> {
+    let mut glyphs: Vec<String> = Vec::new();
+    let mut joined = false;
+    for ch in s.chars() {
+        match glyphs.last_mut() {
+            Some(last) if joined || is_zero_width(ch) => {
+                last.push(ch);
+                joined = ch == '\u{200D}';
+            }
+            _ => glyphs.push(ch.to_string()),
+        }
+    }
+    glyphs
+}
+
+/// Number of terminal columns `glyph` takes up.
+pub fn width(glyph: &str) -> usize {
+    let Some(first) = glyph.chars().next() else {
+        return 0;
+    };
+    // An emoji presentation selector turns text symbols into wide emoji.
+    if is_wide(first) || glyph.contains('\u{FE0F}') {
+        2
+    } else if is_zero_width(first) {
+        0
+    } else {
+        1
+    }
+}
+
+fn is_zero_width(ch: char) -> bool {
+    matches!(ch,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{200B}'..='\u{200F}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{1F3FB}'..='\u{1F3FF}'
+        | '\u{E0020}'..='\u{E007F}'
+        | '\u{E0100}'..='\u{E01EF}')
+}
+
+/// East Asian wide and fullwidth characters and emoji.
+fn is_wide(ch: char) -> bool {
+    matches!(ch,
+        '\u{1100}'..='\u{115F}'
+        | '\u{231A}'..='\u{231B}'
+        | '\u{23E9}'..='\u{23EC}'
+        | '\u{25FD}'..='\u{25FE}'
+        | '\u{2614}'..='\u{2615}'
+        | '\u{26AA}'..='\u{26AB}'
+        | '\u{26BD}'..='\u{26BE}'
+        | '\u{26C4}'..='\u{26C5}'
+        | '\u{2705}'
+        | '\u{270A}'..='\u{270B}'
+        | '\u{2728}'
+        | '\u{274C}'
+        | '\u{2753}'..='\u{2755}'
+        | '\u{2B1B}'..='\u{2B1C}'
+        | '\u{2B50}'
+        | '\u{2E80}'..='\u{303E}'
+        | '\u{3041}'..='\u{33FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{A000}'..='\u{A4CF}'
+        | '\u{AC00}'..='\u{D7A3}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FE30}'..='\u{FE4F}'
+        | '\u{FF00}'..='\u{FF60}'
+        | '\u{FFE0}'..='\u{FFE6}'
+        | '\u{1F004}'
+        | '\u{1F0CF}'
+        | '\u{1F18E}'
+        | '\u{1F191}'..='\u{1F19A}'
+        | '\u{1F300}'..='\u{1F64F}'
+        | '\u{1F680}'..='\u{1F6FF}'
+        | '\u{1F7E0}'..='\u{1F7EB}'
+        | '\u{1F900}'..='\u{1F9FF}'
+        | '\u{1FA70}'..='\u{1FAFF}'
+        | '\u{20000}'..='\u{3FFFD}')
+}
@@ -0,0 +1,36 @@
+use super::cells::{Painter, block};
+use super::color::ColorSpace;
+use super::{RenderedFrame, Renderer};
+use crate::options::RenderOptions;
+use image::{DynamicImage, imageops};
+
+/// Fills every cell with the step of the 24 step xterm gray ramp (colors 232
+/// to 255) closest to the brightness of its 1x2 pixels. Gives smooth monochrome
+/// images on terminals with 256 colors but without truecolor or braille.
+pub struct Grays;
+
+impl Renderer for Grays {
+    fn render(&self, img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+        let mut rgba = img.to_rgba8();
+        if opts.invert {
+            imageops::invert(&mut rgba);
+        }
+
+        let (w, h) = rgba.dimensions();
+        let mut out = Painter::with_colors(
+            (w as usize * 12 + 5) * (h as usize / 2 + 1),
+            ColorSpace::grays(),
+        );
+        for y in (0..h).step_by(2) {
+            for x in 0..w {
+                let mean = block(&rgba, x, y, 1, 2).map(|pixels| {
+                    std::array::from_fn(|c| ((pixels[0][c] as u16 + pixels[1][c] as u16) / 2) as u8)
+                });
+                out.cell(' ', None, mean);
+            }
+            out.end_line();
+        }
+
+        RenderedFrame::from(out.finish())
+    }
+}
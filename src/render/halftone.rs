@@ -0,0 +1,47 @@
+use super::cells::average_luma;
+use super::{RenderMode, RenderedFrame, Renderer, adjust};
+use crate::options::RenderOptions;
+use image::DynamicImage;
+
+/// The order in which the dots of a braille cell are lit as it gets
+/// brighter, by their bit in the code point. The dots grow from the middle
+/// of the cell outwards so they clump like the dots of a print screen.
+const ORDER: [u8; 8] = [1, 5, 4, 2, 3, 6, 0, 7];
+
+/// Turns the image into braille characters like a printed halftone: instead
+/// of comparing every pixel to a threshold, each character lights as many of
+/// its 8 dots as the mean brightness of its 2x4 pixels calls for. Lit dots
+/// stand for the bright parts, so on a light terminal background `--invert`
+/// gives the newspaper look of dark dots for dark parts.
+pub struct Halftone;
+
+impl Renderer for Halftone {
+    fn render(&self, img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+        let mut gray = adjust::grayscale(img, opts.grayscale);
+        adjust::luma(&mut gray, opts);
+
+        let (w, h) = gray.dimensions();
+        let (cell_w, cell_h) = RenderMode::Halftone.cell_size();
+        let mut out =
+            String::with_capacity((w / cell_w + 2) as usize * (h / cell_h + 1) as usize * 3);
+        for y in (0..h).step_by(cell_h as usize) {
+            for x in (0..w).step_by(cell_w as usize) {
+                // Fully transparent cells stay blank.
+                let Some(mean) = average_luma(&gray, x, y, cell_w, cell_h) else {
+                    out.push(' ');
+                    continue;
+                };
+                let mean = u32::from(mean);
+                let level = if opts.invert { 255 - mean } else { mean };
+                let dots = (level * ORDER.len() as u32 + 127) / 255;
+                let bits = ORDER[..dots as usize]
+                    .iter()
+                    .fold(0u32, |bits, &bit| bits | 1 << bit);
+                out.push(char::from_u32(0x2800 + bits).unwrap_or('\u{2800}'));
+            }
+            out.push('\n');
+        }
+
+        RenderedFrame::from(out)
+    }
+}
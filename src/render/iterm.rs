@@ -0,0 +1,33 @@
+use super::{RenderMode, RenderedFrame, Renderer, encode_png};
+use crate::base64;
+use crate::options::RenderOptions;
+use image::{DynamicImage, imageops};
+
+/// Sends the image with iTerm2's inline image protocol (OSC 1337) as a
+/// base64 encoded PNG, stretched over the cells the fitted image covers so
+/// it takes up the same room as the text modes.
+pub struct Iterm;
+
+impl Renderer for Iterm {
+    fn render(&self, img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+        let mut rgba = img.to_rgba8();
+        if opts.invert {
+            imageops::invert(&mut rgba);
+        }
+        let (cell_w, cell_h) = RenderMode::Iterm.cell_size();
+        let cols = img.width().div_ceil(cell_w);
+        let rows = img.height().div_ceil(cell_h);
+
+        let png = encode_png(&rgba);
+        let data = base64::encode(&png);
+        let text = format!(
+            "\x1b]1337;File=inline=1;size={};width={cols};height={rows};preserveAspectRatio=0:{data}\x07\n",
+            png.len()
+        );
+        RenderedFrame {
+            text,
+            rows,
+            image: None,
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use super::{RenderMode, RenderedFrame, Renderer, encode_png};
+use crate::base64;
+use crate::options::{self, RenderOptions};
+use image::{DynamicImage, imageops};
+use std::fmt::Write;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Largest payload the protocol allows in one escape sequence.
+const CHUNK: usize = 4096;
+
+/// Largest id handed out; some terminals read ids as signed 32-bit numbers.
+const MAX_ID: u32 = i32::MAX as u32;
+
+/// Images get their own id so that showing several of them does not
+/// replace the earlier ones. Ids only need to be unique within the terminal
+/// session, so the counter starts at a value that differs from run to run to
+/// keep separate climg runs apart.
+static NEXT_ID: LazyLock<AtomicU32> =
+    LazyLock::new(|| AtomicU32::new((options::random_seed() % u64::from(MAX_ID)) as u32 + 1));
+
+/// The next id, from 1 up to `MAX_ID` and back to 1.
+fn next_id() -> u32 {
+    NEXT_ID
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |id| {
+            Some(if id >= MAX_ID { 1 } else { id + 1 })
+        })
+        .expect("the update always succeeds")
+}
+
+/// Sends the image to the terminal with the kitty graphics protocol as a
+/// base64 encoded PNG, placed over as many cells as the braille output
+/// would take. Understood by kitty, WezTerm and ghostty.
+pub struct Kitty;
+
+impl Renderer for Kitty {
+    fn render(&self, img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+        let mut rgba = img.to_rgba8();
+        if opts.invert {
+            imageops::invert(&mut rgba);
+        }
+        let (cell_w, cell_h) = RenderMode::Kitty.cell_size();
+        let cols = img.width().div_ceil(cell_w);
+        let rows = img.height().div_ceil(cell_h);
+
+        let data = base64::encode(&encode_png(&rgba));
+
+        let id = next_id();
+        let mut out = String::with_capacity(data.len() + data.len() / CHUNK * 16 + 64);
+        let chunks: Vec<&[u8]> = data.as_bytes().chunks(CHUNK).collect();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let more = (i + 1 < chunks.len()) as u8;
+            out.push_str("\x1b_G");
+            if i == 0 {
+                // Transmit and display (a=T) PNG data (f=100) without replies
+                // (q=2), scaled into the cell grid.
+                let _ = write!(out, "a=T,f=100,q=2,i={id},c={cols},r={rows},");
+            }
+            let _ = write!(out, "m={more};");
+            out.push_str(std::str::from_utf8(chunk).expect("base64 is ASCII"));
+            out.push_str("\x1b\\");
+        }
+        out.push('\n');
+
+        RenderedFrame {
+            text: out,
+            rows,
+            image: Some(id),
+        }
+    }
+}
+
+/// Deletes (a=d) the image with the id along with its placements, freeing
+/// its data (d=I), without replies (q=2).
+pub fn delete(id: u32) -> String {
+    format!("\x1b_Ga=d,d=I,i={id},q=2\x1b\\")
+}
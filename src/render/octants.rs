@@ -0,0 +1,77 @@
+use super::cells::{Painter, block, two_color_split};
+use super::{RenderedFrame, Renderer};
+use crate::options::RenderOptions;
+use image::{DynamicImage, imageops};
+
+/// Octant patterns that Unicode 16 leaves out of U+1CD00..U+1CDE5 because
+/// a character for them already exists, sorted by mask.
+const ENCODED_ELSEWHERE: [(u32, char); 26] = [
+    (0x00, ' '),
+    (0x01, '\u{1CEA8}'),
+    (0x02, '\u{1CEAB}'),
+    (0x03, '\u{1FB82}'),
+    (0x05, '▘'),
+    (0x0A, '▝'),
+    (0x0F, '▀'),
+    (0x14, '\u{1FBE6}'),
+    (0x28, '\u{1FBE7}'),
+    (0x3F, '\u{1FB85}'),
+    (0x40, '\u{1CEA3}'),
+    (0x50, '▖'),
+    (0x55, '▌'),
+    (0x5A, '▞'),
+    (0x5F, '▛'),
+    (0x80, '\u{1CEA0}'),
+    (0xA0, '▗'),
+    (0xA5, '▚'),
+    (0xAA, '▐'),
+    (0xAF, '▜'),
+    (0xC0, '▂'),
+    (0xF0, '▄'),
+    (0xF5, '▙'),
+    (0xFA, '▟'),
+    (0xFC, '▆'),
+    (0xFF, '█'),
+];
+
+/// The octant character whose lit eighths are `mask`: bits 0 and 1 are the
+/// left and right dot of the top row, down to bits 6 and 7 for the bottom
+/// row.
+fn octant(mask: u32) -> char {
+    match ENCODED_ELSEWHERE.binary_search_by_key(&mask, |&(m, _)| m) {
+        Ok(i) => ENCODED_ELSEWHERE[i].1,
+        Err(skipped) => char::from_u32(0x1CD00 + mask - skipped as u32).unwrap_or(' '),
+    }
+}
+
+/// Turns the image into lines of Unicode 16 octant characters with 24-bit
+/// colors. Each cell covers 2x4 pixels like braille, but as solid blocks
+/// that leave no gaps between cells, split into the foreground and
+/// background color pair that matches them best.
+pub struct Octants;
+
+impl Renderer for Octants {
+    fn render(&self, img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+        let mut rgba = img.to_rgba8();
+        if opts.invert {
+            imageops::invert(&mut rgba);
+        }
+
+        let (w, h) = rgba.dimensions();
+        let mut out = Painter::new((w as usize * 20 + 5) * (h as usize / 4 + 1), opts);
+        for y in (0..h).step_by(4) {
+            for x in (0..w).step_by(2) {
+                match block(&rgba, x, y, 2, 4) {
+                    Some(pixels) => {
+                        let (mask, fg, bg) = two_color_split(&pixels);
+                        out.cell(octant(mask), Some(fg), Some(bg));
+                    }
+                    None => out.cell(' ', None, None),
+                }
+            }
+            out.end_line();
+        }
+
+        RenderedFrame::from(out.finish())
+    }
+}
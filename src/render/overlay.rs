@@ -0,0 +1,84 @@
+use super::transform::Length;
+use crate::options::RenderOptions;
+use image::{DynamicImage, RgbaImage, imageops};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// A second image drawn over the main one, loaded once for all inputs.
+#[derive(Clone)]
+pub struct Overlay(Arc<RgbaImage>);
+
+impl Overlay {
+    pub fn new(img: DynamicImage) -> Self {
+        Overlay(Arc::new(img.into_rgba8()))
+    }
+}
+
+impl fmt::Debug for Overlay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Overlay({}x{})", self.0.width(), self.0.height())
+    }
+}
+
+/// Where --overlay-pos puts the overlay, given as `X,Y`. Pixels count from
+/// the top left corner; percentages are of the room left beside the overlay,
+/// so `100%,100%` is the bottom right corner and `50%,50%` the center.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Position {
+    pub x: Length,
+    pub y: Length,
+}
+
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((x, y)) = s.split_once(',') else {
+            return Err(format!(
+                "`{s}` is not a position such as `10,10` or `100%,100%`"
+            ));
+        };
+        Ok(Position {
+            x: x.parse()?,
+            y: y.parse()?,
+        })
+    }
+}
+
+impl TryFrom<String> for Position {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Blends the overlay over the image at its position, `opts.overlay_alpha`
+/// scaling its opacity. Leaves the image alone without an overlay.
+pub fn apply<'a>(img: Cow<'a, DynamicImage>, opts: &RenderOptions) -> Cow<'a, DynamicImage> {
+    let Some(Overlay(overlay)) = &opts.overlay else {
+        return img;
+    };
+    let mut rgba = img.to_rgba8();
+    let room = |full: u32, size: u32, at: Length| match at {
+        Length::Pixels(px) => px as i64,
+        Length::Percent(_) => at.resolve(full.saturating_sub(size)) as i64,
+    };
+    let x = room(rgba.width(), overlay.width(), opts.overlay_pos.x);
+    let y = room(rgba.height(), overlay.height(), opts.overlay_pos.y);
+    log::info!("overlay at {x},{y} alpha={}", opts.overlay_alpha);
+    if opts.overlay_alpha >= 1.0 {
+        imageops::overlay(&mut rgba, overlay.as_ref(), x, y);
+    } else {
+        let mut faded = overlay.as_ref().clone();
+        for pixel in faded.pixels_mut() {
+            pixel[3] = (pixel[3] as f32 * opts.overlay_alpha).round() as u8;
+        }
+        imageops::overlay(&mut rgba, &faded, x, y);
+    }
+    Cow::Owned(DynamicImage::ImageRgba8(rgba))
+}
@@ -0,0 +1,116 @@
+use super::cells::Rgb;
+use super::color::parse_hex;
+use crate::error::{Error, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+/// Color schemes the output can be snapped to so it fits the terminal's
+/// theme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Palette {
+    /// Gruvbox
+    Gruvbox,
+    /// Solarized
+    Solarized,
+    /// Nord
+    Nord,
+    /// Dracula
+    Dracula,
+    /// Catppuccin Mocha
+    Catppuccin,
+}
+
+const GRUVBOX: &[u32] = &[
+    0x282828, 0x3c3836, 0x504945, 0x665c54, 0x7c6f64, 0x928374, 0xa89984, 0xbdae93, 0xd5c4a1,
+    0xebdbb2, 0xfbf1c7, 0xcc241d, 0xfb4934, 0x98971a, 0xb8bb26, 0xd79921, 0xfabd2f, 0x458588,
+    0x83a598, 0xb16286, 0xd3869b, 0x689d6a, 0x8ec07c, 0xd65d0e, 0xfe8019,
+];
+
+const SOLARIZED: &[u32] = &[
+    0x002b36, 0x073642, 0x586e75, 0x657b83, 0x839496, 0x93a1a1, 0xeee8d5, 0xfdf6e3, 0xb58900,
+    0xcb4b16, 0xdc322f, 0xd33682, 0x6c71c4, 0x268bd2, 0x2aa198, 0x859900,
+];
+
+const NORD: &[u32] = &[
+    0x2e3440, 0x3b4252, 0x434c5e, 0x4c566a, 0xd8dee9, 0xe5e9f0, 0xeceff4, 0x8fbcbb, 0x88c0d0,
+    0x81a1c1, 0x5e81ac, 0xbf616a, 0xd08770, 0xebcb8b, 0xa3be8c, 0xb48ead,
+];
+
+const DRACULA: &[u32] = &[
+    0x21222c, 0x282a36, 0x44475a, 0x6272a4, 0xf8f8f2, 0x8be9fd, 0x50fa7b, 0xffb86c, 0xff79c6,
+    0xbd93f9, 0xff5555, 0xf1fa8c,
+];
+
+const CATPPUCCIN: &[u32] = &[
+    0x11111b, 0x181825, 0x1e1e2e, 0x313244, 0x45475a, 0x585b70, 0x6c7086, 0x7f849c, 0x9399b2,
+    0xa6adc8, 0xbac2de, 0xcdd6f4, 0xf5e0dc, 0xf2cdcd, 0xf5c2e7, 0xcba6f7, 0xf38ba8, 0xeba0ac,
+    0xfab387, 0xf9e2af, 0xa6e3a1, 0x94e2d5, 0x89dceb, 0x74c7ec, 0x89b4fa, 0xb4befe,
+];
+
+impl Palette {
+    pub fn colors(self) -> Vec<Rgb> {
+        let hex = match self {
+            Palette::Gruvbox => GRUVBOX,
+            Palette::Solarized => SOLARIZED,
+            Palette::Nord => NORD,
+            Palette::Dracula => DRACULA,
+            Palette::Catppuccin => CATPPUCCIN,
+        };
+        hex.iter()
+            .map(|&c| [(c >> 16) as u8, (c >> 8) as u8, c as u8])
+            .collect()
+    }
+}
+
+/// Reads a palette from a GIMP `.gpl` file or from a file listing one
+/// `#rrggbb` color per line. Blank lines and lines starting with `;` are
+/// skipped in the latter.
+pub fn load_palette(path: &Path) -> Result<Vec<Rgb>> {
+    let text = fs::read_to_string(path).map_err(|e| Error::open(path, e))?;
+    let invalid =
+        |line: usize, msg: &str| Error::Config(format!("{}:{line}: {msg}", path.display()));
+
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()));
+    let gimp = text
+        .lines()
+        .next()
+        .is_some_and(|l| l.trim() == "GIMP Palette");
+    if gimp {
+        lines.next();
+    }
+    let mut colors = Vec::new();
+    for (n, line) in lines {
+        if line.is_empty() {
+            continue;
+        }
+        if gimp {
+            if line.starts_with('#') || line.starts_with("Name:") || line.starts_with("Columns:") {
+                continue;
+            }
+            let channels: Option<Vec<u8>> = line
+                .split_whitespace()
+                .take(3)
+                .map(|c| c.parse().ok())
+                .collect();
+            match channels.as_deref() {
+                Some(&[r, g, b]) => colors.push([r, g, b]),
+                _ => return Err(invalid(n, "expected `<red> <green> <blue> [name]`")),
+            }
+        } else if !line.starts_with(';') {
+            colors.push(parse_hex(line).map_err(|msg| invalid(n, &msg))?);
+        }
+    }
+    if colors.is_empty() {
+        return Err(Error::Config(format!(
+            "{}: the palette is empty",
+            path.display()
+        )));
+    }
+    Ok(colors)
+}
@@ -0,0 +1,42 @@
+use super::cells::{Painter, block, two_color_split};
+use super::{RenderedFrame, Renderer};
+use crate::options::RenderOptions;
+use image::{DynamicImage, imageops};
+
+/// Quadrant characters indexed by their lit quarters: bit 0 is the upper
+/// left, bit 1 the upper right, bit 2 the lower left and bit 3 the lower
+/// right quarter.
+const QUADRANTS: [char; 16] = [
+    ' ', '▘', '▝', '▀', '▖', '▌', '▞', '▛', '▗', '▚', '▐', '▜', '▄', '▙', '▟', '█',
+];
+
+/// Turns the image into lines of quadrant characters with 24-bit colors.
+/// Each cell covers 2x2 pixels, split into the foreground and background
+/// color pair that matches them best.
+pub struct Quadrants;
+
+impl Renderer for Quadrants {
+    fn render(&self, img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+        let mut rgba = img.to_rgba8();
+        if opts.invert {
+            imageops::invert(&mut rgba);
+        }
+
+        let (w, h) = rgba.dimensions();
+        let mut out = Painter::new((w as usize * 20 + 5) * (h as usize / 2 + 1), opts);
+        for y in (0..h).step_by(2) {
+            for x in (0..w).step_by(2) {
+                match block(&rgba, x, y, 2, 2) {
+                    Some(pixels) => {
+                        let (mask, fg, bg) = two_color_split(&pixels);
+                        out.cell(QUADRANTS[mask as usize], Some(fg), Some(bg));
+                    }
+                    None => out.cell(' ', None, None),
+                }
+            }
+            out.end_line();
+        }
+
+        RenderedFrame::from(out.finish())
+    }
+}
@@ -0,0 +1,54 @@
+use super::cells::{Painter, block, two_color_split};
+use super::{RenderedFrame, Renderer};
+use crate::options::RenderOptions;
+use image::{DynamicImage, imageops};
+
+/// The sextant character whose lit sixths are `mask`: bit 0 is the upper
+/// left, bit 1 the upper right, bits 2 and 3 the middle row and bits 4 and 5
+/// the lower row. U+1FB00..U+1FB3B cover every pattern except the four that
+/// already exist as space, `▌`, `▐` and `█`, which is why the offset skips
+/// over them.
+fn sextant(mask: u32) -> char {
+    match mask {
+        0 => ' ',
+        0b010101 => '▌',
+        0b101010 => '▐',
+        0b111111 => '█',
+        _ => {
+            let skipped = (mask > 0b010101) as u32 + (mask > 0b101010) as u32;
+            char::from_u32(0x1FB00 + mask - 1 - skipped).unwrap_or(' ')
+        }
+    }
+}
+
+/// Turns the image into lines of sextant characters from Unicode 13's
+/// "Symbols for Legacy Computing" with 24-bit colors. Each cell covers 2x3
+/// pixels, split into the foreground and background color pair that
+/// matches them best.
+pub struct Sextants;
+
+impl Renderer for Sextants {
+    fn render(&self, img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+        let mut rgba = img.to_rgba8();
+        if opts.invert {
+            imageops::invert(&mut rgba);
+        }
+
+        let (w, h) = rgba.dimensions();
+        let mut out = Painter::new((w as usize * 20 + 5) * (h as usize / 3 + 1), opts);
+        for y in (0..h).step_by(3) {
+            for x in (0..w).step_by(2) {
+                match block(&rgba, x, y, 2, 3) {
+                    Some(pixels) => {
+                        let (mask, fg, bg) = two_color_split(&pixels);
+                        out.cell(sextant(mask), Some(fg), Some(bg));
+                    }
+                    None => out.cell(' ', None, None),
+                }
+            }
+            out.end_line();
+        }
+
+        RenderedFrame::from(out.finish())
+    }
+}
@@ -0,0 +1,74 @@
+use super::cells::{Painter, average_luma, block, two_color_split};
+use super::{RenderMode, RenderedFrame, Renderer, adjust};
+use crate::options::RenderOptions;
+use image::{DynamicImage, imageops};
+
+/// From empty to full.
+const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+
+/// Draws every cell as the shade character matching the mean brightness of
+/// its 2x4 pixels. With `color`, the pixels are split into the foreground
+/// and background color pair that fits them best instead, and the shade
+/// shows how much of the cell the foreground covers.
+pub struct Shades {
+    pub color: bool,
+}
+
+impl Renderer for Shades {
+    fn render(&self, img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+        if self.color {
+            return color_shades(img, opts);
+        }
+        let mut gray = adjust::grayscale(img, opts.grayscale);
+        adjust::luma(&mut gray, opts);
+
+        let (w, h) = gray.dimensions();
+        let (cell_w, cell_h) = RenderMode::Shades.cell_size();
+        let mut out =
+            String::with_capacity((w / cell_w + 2) as usize * (h / cell_h + 1) as usize * 3);
+        for y in (0..h).step_by(cell_h as usize) {
+            for x in (0..w).step_by(cell_w as usize) {
+                // Fully transparent cells stay empty.
+                let mean = average_luma(&gray, x, y, cell_w, cell_h);
+                let level = mean.map_or(0, |mean| {
+                    let mean = u32::from(mean);
+                    let level = if opts.invert { 255 - mean } else { mean };
+                    (level * (SHADES.len() as u32 - 1) + 127) / 255
+                });
+                out.push(SHADES[level as usize]);
+            }
+            out.push('\n');
+        }
+
+        RenderedFrame::from(out)
+    }
+}
+
+fn color_shades(img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+    let mut rgba = img.to_rgba8();
+    if opts.invert {
+        imageops::invert(&mut rgba);
+    }
+
+    let (w, h) = rgba.dimensions();
+    let (cell_w, cell_h) = RenderMode::ColorShades.cell_size();
+    let mut out = Painter::new(
+        (w / cell_w + 1) as usize * (h / cell_h + 1) as usize * 40,
+        opts,
+    );
+    for y in (0..h).step_by(cell_h as usize) {
+        for x in (0..w).step_by(cell_w as usize) {
+            let Some(pixels) = block(&rgba, x, y, cell_w, cell_h) else {
+                out.cell(' ', None, None);
+                continue;
+            };
+            let (mask, fg, bg) = two_color_split(&pixels);
+            let covered = mask.count_ones() * (SHADES.len() as u32 - 1);
+            let level = (covered + pixels.len() as u32 / 2) / pixels.len() as u32;
+            out.cell(SHADES[level as usize], Some(fg), Some(bg));
+        }
+        out.end_line();
+    }
+
+    RenderedFrame::from(out.finish())
+}
@@ -0,0 +1,94 @@
+use super::cells::ALPHA_CUTOFF;
+use super::{RenderMode, RenderedFrame, Renderer};
+use crate::options::RenderOptions;
+use color_quant::NeuQuant;
+use image::{DynamicImage, imageops};
+use std::fmt::Write;
+
+/// Encodes the image as DEC sixel graphics for terminals such as xterm,
+/// foot and mlterm. The colors are reduced to a palette of at most
+/// `opts.sixel_colors` entries with NeuQuant.
+pub struct Sixel;
+
+impl Renderer for Sixel {
+    fn render(&self, img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+        let mut rgba = img.to_rgba8();
+        if opts.invert {
+            imageops::invert(&mut rgba);
+        }
+        let (w, h) = rgba.dimensions();
+
+        let colors = opts.sixel_colors.clamp(2, 256) as usize;
+        let quant = NeuQuant::new(10, colors, rgba.as_raw());
+        let palette = quant.color_map_rgb();
+        let indices: Vec<Option<usize>> = rgba
+            .pixels()
+            .map(|p| (p[3] >= ALPHA_CUTOFF).then(|| quant.index_of(&p.0)))
+            .collect();
+        log::debug!("sixel palette of {} colors", palette.len() / 3);
+
+        // P2=1 leaves pixels that no color is drawn in transparent.
+        let mut out = format!("\x1bP0;1;0q\"1;1;{w};{h}");
+        for (i, rgb) in palette.chunks_exact(3).enumerate() {
+            let [r, g, b] = [0, 1, 2].map(|c| rgb[c] as u32 * 100 / 255);
+            let _ = write!(out, "#{i};2;{r};{g};{b}");
+        }
+
+        let mut rows: Vec<Option<Vec<u8>>> = vec![None; palette.len() / 3];
+        for band in (0..h).step_by(6) {
+            // Collect the six pixel high strip of every color used in the band.
+            for dy in 0..6.min(h - band) {
+                let y = band + dy;
+                for x in 0..w {
+                    if let Some(i) = indices[(y * w + x) as usize] {
+                        let row = rows[i].get_or_insert_with(|| vec![0; w as usize]);
+                        row[x as usize] |= 1 << dy;
+                    }
+                }
+            }
+
+            let mut first = true;
+            for (i, row) in rows.iter_mut().enumerate() {
+                let Some(row) = row.take() else {
+                    continue;
+                };
+                if !first {
+                    // Carriage return: draw the next color over the same band.
+                    out.push('$');
+                }
+                first = false;
+                let _ = write!(out, "#{i}");
+                encode_row(&mut out, &row);
+            }
+            out.push('-');
+        }
+        out.push_str("\x1b\\\n");
+
+        let rows = h.div_ceil(RenderMode::Sixel.cell_size().1);
+        RenderedFrame {
+            text: out,
+            rows,
+            image: None,
+        }
+    }
+}
+
+/// Appends one color's strip of sixels, run-length encoding repeats.
+fn encode_row(out: &mut String, row: &[u8]) {
+    // Trailing empty sixels draw nothing.
+    let len = row.iter().rposition(|&bits| bits != 0).map_or(0, |i| i + 1);
+    let mut x = 0;
+    while x < len {
+        let bits = row[x];
+        let run = row[x..len].iter().take_while(|&&b| b == bits).count();
+        let ch = (0x3F + bits) as char;
+        if run >= 4 {
+            let _ = write!(out, "!{run}{ch}");
+        } else {
+            for _ in 0..run {
+                out.push(ch);
+            }
+        }
+        x += run;
+    }
+}
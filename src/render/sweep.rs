@@ -0,0 +1,67 @@
+use super::{RenderMode, RenderedFrame, adjust, prepare, threshold};
+use crate::options::RenderOptions;
+use image::DynamicImage;
+
+/// Offsets from the automatic threshold the previews are drawn at.
+const STEPS: [i32; 5] = [-40, -20, 0, 20, 40];
+
+/// Spaces between two previews.
+const GAP: usize = 2;
+
+/// A row of small braille previews of the image at thresholds around the
+/// automatic one (or --threshold), each labeled with its value underneath.
+pub fn sweep(img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+    let mode = match opts.mode {
+        RenderMode::ColorBraille => RenderMode::ColorBraille,
+        _ => RenderMode::Braille,
+    };
+    let (cell_w, cell_h) = mode.cell_size();
+    let img = prepare(img, opts, (cell_w, cell_h));
+    let count = STEPS.len();
+    let cols = (img.width() / cell_w) as usize;
+    let cells = (cols.saturating_sub(GAP * (count - 1)) / count).max(1);
+    let small = img.resize(
+        cells as u32 * cell_w,
+        u32::MAX,
+        image::imageops::FilterType::Triangle,
+    );
+
+    let center = opts.threshold.unwrap_or_else(|| {
+        let mut gray = adjust::grayscale(&small, opts.grayscale);
+        adjust::luma(&mut gray, opts);
+        threshold::otsu(&gray)
+    }) as i32;
+    log::info!("sweep around threshold={center}");
+    let previews: Vec<(u8, String)> = STEPS
+        .iter()
+        .map(|step| {
+            let t = (center + step).clamp(0, 255) as u8;
+            let opts = RenderOptions {
+                mode,
+                threshold: Some(t),
+                hysteresis: None,
+                ..opts.clone()
+            };
+            (t, mode.renderer().render(&small, &opts).text)
+        })
+        .collect();
+
+    let gap = " ".repeat(GAP);
+    let mut out = String::new();
+    let lines: Vec<Vec<&str>> = previews.iter().map(|(_, p)| p.lines().collect()).collect();
+    for row in 0..lines[0].len() {
+        let row: Vec<&str> = lines
+            .iter()
+            .map(|l| l.get(row).copied().unwrap_or(""))
+            .collect();
+        out.push_str(&row.join(&gap));
+        out.push('\n');
+    }
+    let labels: Vec<String> = previews
+        .iter()
+        .map(|(t, _)| format!("{:^cells$}", format!("t={t}")))
+        .collect();
+    out.push_str(labels.join(&gap).trim_end());
+    out.push('\n');
+    RenderedFrame::from(out)
+}
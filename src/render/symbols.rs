@@ -0,0 +1,125 @@
+use super::cells::{Painter, block, fit_mask};
+use super::{RenderMode, RenderedFrame, Renderer};
+use crate::options::RenderOptions;
+use image::{DynamicImage, imageops};
+
+const COLS: u32 = 4;
+const ROWS: u32 = 8;
+
+/// A character together with the pixels of a 4x8 cell it covers, bit
+/// `row * 4 + col` for each pixel.
+struct Symbol {
+    ch: char,
+    mask: u32,
+}
+
+/// The mask lighting the pixels for which `lit(col, row)` holds.
+fn mask_of(lit: impl Fn(u32, u32) -> bool) -> u32 {
+    let mut mask = 0;
+    for row in 0..ROWS {
+        for col in 0..COLS {
+            if lit(col, row) {
+                mask |= 1 << (row * COLS + col);
+            }
+        }
+    }
+    mask
+}
+
+/// Every character the renderer may pick from. Block elements come first so
+/// they win ties against braille patterns that cover the same pixels, and a
+/// character is left out when it or its inverse is already in the set,
+/// since swapping the colors draws the same thing.
+fn symbols() -> Vec<Symbol> {
+    let mut candidates = vec![
+        (' ', 0),
+        ('▀', mask_of(|_, row| row < 4)),
+        ('▌', mask_of(|col, _| col < 2)),
+        ('▎', mask_of(|col, _| col < 1)),
+        ('▊', mask_of(|col, _| col < 3)),
+        ('▔', mask_of(|_, row| row < 1)),
+    ];
+    for (eighths, ch) in (1..8).zip("▁▂▃▄▅▆▇".chars()) {
+        candidates.push((ch, mask_of(|_, row| row >= ROWS - eighths)));
+    }
+    for (quarters, ch) in (1u32..16).zip("▘▝▀▖▌▞▛▗▚▐▜▄▙▟█".chars()) {
+        let lit = |col: u32, row: u32| quarters & 1 << ((row / 4) * 2 + col / 2) != 0;
+        candidates.push((ch, mask_of(lit)));
+    }
+    // Shades are drawn as stipple patterns, which is roughly how they look.
+    candidates.push((
+        '░',
+        mask_of(|col, row| row % 2 == 0 && col % 2 == row / 2 % 2),
+    ));
+    candidates.push(('▒', mask_of(|col, row| (col + row) % 2 == 0)));
+    for bits in 1u32..256 {
+        // Each braille dot stands for a 2x2 block of pixels, see DOTS in
+        // braille.rs for the bit order.
+        let lit = |col: u32, row: u32| {
+            let (dx, dy) = (col / 2, row / 2);
+            let bit = match (dx, dy) {
+                (0, 3) => 6,
+                (1, 3) => 7,
+                (dx, dy) => dx * 3 + dy,
+            };
+            bits & 1 << bit != 0
+        };
+        let ch = char::from_u32(0x2800 + bits).unwrap_or('\u{2800}');
+        candidates.push((ch, mask_of(lit)));
+    }
+
+    let full = mask_of(|_, _| true);
+    let mut symbols: Vec<Symbol> = Vec::with_capacity(candidates.len());
+    for (ch, mask) in candidates {
+        if !symbols
+            .iter()
+            .any(|s| s.mask == mask || s.mask == !mask & full)
+        {
+            symbols.push(Symbol { ch, mask });
+        }
+    }
+    symbols
+}
+
+/// Turns the image into lines of block, shade and braille characters with
+/// 24-bit colors. Each cell covers 4x8 pixels and gets the character and
+/// foreground and background color pair that reproduce them best, which
+/// keeps both sharp edges and smooth areas.
+pub struct Symbols;
+
+impl Renderer for Symbols {
+    fn render(&self, img: &DynamicImage, opts: &RenderOptions) -> RenderedFrame {
+        let mut rgba = img.to_rgba8();
+        if opts.invert {
+            imageops::invert(&mut rgba);
+        }
+        let symbols = symbols();
+
+        let (w, h) = rgba.dimensions();
+        let (cell_w, cell_h) = RenderMode::Symbols.cell_size();
+        let mut out = Painter::new(
+            (w / cell_w + 1) as usize * (h / cell_h + 1) as usize * 40,
+            opts,
+        );
+        for y in (0..h).step_by(cell_h as usize) {
+            for x in (0..w).step_by(cell_w as usize) {
+                let Some(pixels) = block(&rgba, x, y, cell_w, cell_h) else {
+                    out.cell(' ', None, None);
+                    continue;
+                };
+                let (_, ch, fg, bg) = symbols
+                    .iter()
+                    .map(|symbol| {
+                        let (err, fg, bg) = fit_mask(&pixels, symbol.mask);
+                        (err, symbol.ch, fg, bg)
+                    })
+                    .min_by_key(|&(err, ..)| err)
+                    .expect("the symbol set is not empty");
+                out.cell(ch, Some(fg), Some(bg));
+            }
+            out.end_line();
+        }
+
+        RenderedFrame::from(out.finish())
+    }
+}
@@ -0,0 +1,411 @@
+use super::cells::ALPHA_CUTOFF;
+use crate::options::RenderOptions;
+use clap::ValueEnum;
+use image::{GrayAlphaImage, GrayImage, Luma, LumaA};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// How the brightness cutoff of the braille modes is chosen when
+/// --threshold does not fix it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThresholdMode {
+    /// One cutoff for the whole image, picked with Otsu's method
+    #[default]
+    Otsu,
+    /// A cutoff for every pixel from the mean brightness around it (see
+    /// --threshold-window and --threshold-offset), for unevenly lit photos
+    Adaptive,
+    /// Sauvola's method: the local mean, lowered where the contrast around
+    /// is low (see --threshold-k), for scanned text and whiteboards
+    Sauvola,
+    /// Niblack's method: the local mean plus k times the local standard
+    /// deviation (see --threshold-k)
+    Niblack,
+    /// Otsu's method on every tile of a grid (see --threshold-tiles), blended
+    /// between the tile centers, for vignetting and gradients
+    TiledOtsu,
+}
+
+impl ThresholdMode {
+    /// The `k` of Sauvola's and Niblack's methods when --threshold-k is not
+    /// given.
+    pub fn default_k(self) -> f32 {
+        match self {
+            ThresholdMode::Niblack => -0.2,
+            _ => 0.2,
+        }
+    }
+}
+
+/// A grid of tiles the image is divided into, given as `COLSxROWS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Tiles {
+    pub cols: u32,
+    pub rows: u32,
+}
+
+impl Default for Tiles {
+    fn default() -> Self {
+        Self { cols: 4, rows: 4 }
+    }
+}
+
+impl Tiles {
+    /// The grid for a `w`x`h` image, with no more tiles than pixels.
+    pub fn fit(self, (w, h): (u32, u32)) -> Tiles {
+        Tiles {
+            cols: self.cols.min(w.max(1)),
+            rows: self.rows.min(h.max(1)),
+        }
+    }
+
+    /// The pixels of the tile at `(col, row)` of a `w`x`h` image.
+    pub fn pixels(
+        self,
+        col: u32,
+        row: u32,
+        (w, h): (u32, u32),
+    ) -> impl Iterator<Item = (u32, u32)> {
+        let xs = col * w / self.cols..(col + 1) * w / self.cols;
+        let ys = row * h / self.rows..(row + 1) * h / self.rows;
+        ys.flat_map(move |y| xs.clone().map(move |x| (x, y)))
+    }
+
+    /// The `value` of the tiles around pixel `(x, y)`, blended bilinearly
+    /// between the tile centers. Beyond the outer centers the values of the
+    /// outer tiles are kept.
+    pub fn blend(self, x: u32, y: u32, (w, h): (u32, u32), value: impl Fn(u32, u32) -> f32) -> f32 {
+        // Position in tile units, measured from the first center.
+        let place = |p: u32, n: u32, len: u32| {
+            let at = (p as f32 + 0.5) * n as f32 / len as f32 - 0.5;
+            let at = at.clamp(0.0, (n - 1) as f32);
+            let i = (at as u32).min(n.saturating_sub(2));
+            (i, (i + 1).min(n - 1), at - i as f32)
+        };
+        let (c0, c1, fx) = place(x, self.cols, w);
+        let (r0, r1, fy) = place(y, self.rows, h);
+        let top = value(c0, r0) + (value(c1, r0) - value(c0, r0)) * fx;
+        let bottom = value(c0, r1) + (value(c1, r1) - value(c0, r1)) * fx;
+        top + (bottom - top) * fy
+    }
+}
+
+impl FromStr for Tiles {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let count = |n: &str| n.trim().parse::<u32>().ok().filter(|&n| n > 0);
+        match s.split_once('x').map(|(c, r)| (count(c), count(r))) {
+            Some((Some(cols), Some(rows))) => Ok(Tiles { cols, rows }),
+            _ => Err(format!("`{s}` is not a grid such as `4x4`")),
+        }
+    }
+}
+
+impl TryFrom<String> for Tiles {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// The brightness cutoff of every pixel.
+pub enum Threshold {
+    Global(u8),
+    Local(GrayImage),
+}
+
+impl Threshold {
+    pub fn at(&self, x: u32, y: u32) -> u8 {
+        match self {
+            Threshold::Global(t) => *t,
+            Threshold::Local(map) => map.get_pixel(x, y)[0],
+        }
+    }
+}
+
+/// Picks the cutoff for `gray` as the options ask.
+pub fn threshold(gray: &GrayAlphaImage, opts: &RenderOptions) -> Threshold {
+    if let Some(t) = opts.threshold {
+        log::info!("threshold={t} method=manual");
+        return Threshold::Global(t);
+    }
+    if let Some(p) = opts.threshold_percentile {
+        let t = percentile(gray, p, opts.invert);
+        log::info!("threshold={t} method=percentile percentile={p}");
+        return Threshold::Global(t);
+    }
+    match opts.threshold_mode {
+        ThresholdMode::Otsu => {
+            let t = otsu(gray);
+            log::info!("threshold={t} method=otsu");
+            Threshold::Global(t)
+        }
+        ThresholdMode::Adaptive => {
+            let (window, offset) = (opts.threshold_window, opts.threshold_offset);
+            log::info!("method=adaptive window={window} offset={offset}");
+            let offset = offset as f64;
+            Threshold::Local(local(gray, window, |mean, _| mean - offset))
+        }
+        ThresholdMode::Sauvola => {
+            let (window, k) = (opts.threshold_window, opts.threshold_k);
+            log::info!("method=sauvola window={window} k={k}");
+            let k = k as f64;
+            // 128 is the largest standard deviation 8-bit values can have.
+            Threshold::Local(local(gray, window, |mean, sd| {
+                mean * (1.0 + k * (sd / 128.0 - 1.0))
+            }))
+        }
+        ThresholdMode::Niblack => {
+            let (window, k) = (opts.threshold_window, opts.threshold_k);
+            log::info!("method=niblack window={window} k={k}");
+            let k = k as f64;
+            Threshold::Local(local(gray, window, |mean, sd| mean + k * sd))
+        }
+        ThresholdMode::TiledOtsu => {
+            let tiles = opts.threshold_tiles;
+            log::info!("method=tiled-otsu tiles={}x{}", tiles.cols, tiles.rows);
+            Threshold::Local(tiled_otsu(gray, tiles))
+        }
+    }
+}
+
+/// Hysteresis thresholding: sets the pixels at least `hi` bright, and those
+/// at least `lo` bright connected to them through other such pixels, to 255
+/// and all others to 0. With `invert` the dark side is grown instead: pixels
+/// below `lo` and those below `hi` connected to them become 0.
+pub fn hysteresis(gray: &mut GrayAlphaImage, (lo, hi): (u8, u8), invert: bool) {
+    log::info!("method=hysteresis lo={lo} hi={hi}");
+    let (w, h) = gray.dimensions();
+    let lit = |v: u8, cutoff: u8| if invert { v < cutoff } else { v >= cutoff };
+    let (strong, weak) = if invert { (lo, hi) } else { (hi, lo) };
+    let mut on = vec![false; (w * h) as usize];
+    let mut stack: Vec<(u32, u32)> = Vec::new();
+    for (x, y, LumaA([v, a])) in gray.enumerate_pixels() {
+        if *a >= ALPHA_CUTOFF && lit(*v, strong) {
+            on[(y * w + x) as usize] = true;
+            stack.push((x, y));
+        }
+    }
+    while let Some((x, y)) = stack.pop() {
+        for (nx, ny) in neighbours(x, y, w, h) {
+            let i = (ny * w + nx) as usize;
+            let LumaA([v, a]) = *gray.get_pixel(nx, ny);
+            if !on[i] && a >= ALPHA_CUTOFF && lit(v, weak) {
+                on[i] = true;
+                stack.push((nx, ny));
+            }
+        }
+    }
+    for (pixel, on) in gray.pixels_mut().zip(on) {
+        pixel[0] = if on != invert { 255 } else { 0 };
+    }
+}
+
+/// The up to 8 pixels around `(x, y)` in a `w`x`h` image.
+fn neighbours(x: u32, y: u32, w: u32, h: u32) -> impl Iterator<Item = (u32, u32)> {
+    let xs = x.saturating_sub(1)..(x + 2).min(w);
+    let ys = y.saturating_sub(1)..(y + 2).min(h);
+    ys.flat_map(move |ny| xs.clone().map(move |nx| (nx, ny)))
+        .filter(move |&p| p != (x, y))
+}
+
+/// The threshold that lights the closest to `p` percent of the opaque
+/// pixels: those at least as bright, or with `invert` those darker.
+fn percentile(gray: &GrayAlphaImage, p: f32, invert: bool) -> u8 {
+    let hist = histogram(gray.pixels());
+    let total: u32 = hist.iter().sum();
+    let target = (total as f64 * p as f64 / 100.0).round() as i64;
+    let mut below = 0i64;
+    let mut best = (i64::MAX, 128);
+    for (t, &h) in hist.iter().enumerate() {
+        let lit = if invert { below } else { total as i64 - below };
+        if (lit - target).abs() < best.0 {
+            best = ((lit - target).abs(), t as u8);
+        }
+        below += h as i64;
+    }
+    best.1
+}
+
+/// Otsu's threshold over the brightness of the opaque pixels.
+pub fn otsu(img: &GrayAlphaImage) -> u8 {
+    otsu_histogram(&histogram(img.pixels())).unwrap_or(128)
+}
+
+/// How many of the opaque `pixels` there are of every brightness.
+fn histogram<'a>(pixels: impl Iterator<Item = &'a LumaA<u8>>) -> [u32; 256] {
+    let mut hist = [0u32; 256];
+    for LumaA([v, a]) in pixels {
+        if *a >= ALPHA_CUTOFF {
+            hist[*v as usize] += 1;
+        }
+    }
+    hist
+}
+
+/// Otsu's threshold for a histogram, or `None` when it is empty.
+fn otsu_histogram(hist: &[u32; 256]) -> Option<u8> {
+    let total: u32 = hist.iter().sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut sum_total: f64 = 0.0;
+    for (i, &h) in hist.iter().enumerate() {
+        sum_total += (i as f64) * (h as f64);
+    }
+
+    let mut sum_b: f64 = 0.0;
+    let mut w_b: f64 = 0.0;
+    let mut w_f: f64;
+    let mut max_var: f64 = -1.0;
+    let mut threshold: u8 = 0;
+
+    for (t, &h) in hist.iter().enumerate() {
+        w_b += h as f64;
+        if w_b == 0.0 {
+            continue;
+        }
+        w_f = (total as f64) - w_b;
+        if w_f == 0.0 {
+            break;
+        }
+        sum_b += (t as f64) * (h as f64);
+
+        let m_b = sum_b / w_b;
+        let m_f = (sum_total - sum_b) / w_f;
+
+        let var_between = w_b * w_f * (m_b - m_f) * (m_b - m_f);
+        if var_between > max_var {
+            max_var = var_between;
+            threshold = t as u8;
+        }
+    }
+
+    Some(threshold)
+}
+
+/// Otsu's threshold of every tile of the grid, blended bilinearly between
+/// the tile centers. Tiles without opaque pixels take the threshold of the
+/// whole image.
+fn tiled_otsu(gray: &GrayAlphaImage, tiles: Tiles) -> GrayImage {
+    let dims = gray.dimensions();
+    let tiles = tiles.fit(dims);
+    let global = otsu(gray);
+    let centers: Vec<f32> = (0..tiles.rows)
+        .flat_map(|row| (0..tiles.cols).map(move |col| (col, row)))
+        .map(|(col, row)| {
+            let pixels = tiles.pixels(col, row, dims);
+            let hist = histogram(pixels.map(|(x, y)| gray.get_pixel(x, y)));
+            otsu_histogram(&hist).unwrap_or(global) as f32
+        })
+        .collect();
+
+    GrayImage::from_fn(dims.0, dims.1, |x, y| {
+        let t = tiles.blend(x, y, dims, |c, r| centers[(r * tiles.cols + c) as usize]);
+        Luma([t.round() as u8])
+    })
+}
+
+/// Sums of the brightness of the opaque pixels, of its square and of their
+/// number over every rectangle starting at the top left corner, so the sums
+/// over any window take four lookups.
+struct Integral {
+    width: usize,
+    sums: Vec<u64>,
+    squares: Vec<u64>,
+    counts: Vec<u32>,
+}
+
+/// Number, sum and sum of squares of the brightness of some pixels.
+#[derive(Clone, Copy)]
+struct Sums {
+    count: u32,
+    sum: u64,
+    squares: u64,
+}
+
+impl Sums {
+    /// Mean and standard deviation, or `None` without any pixels.
+    fn stats(self) -> Option<(f64, f64)> {
+        if self.count == 0 {
+            return None;
+        }
+        let n = self.count as f64;
+        let mean = self.sum as f64 / n;
+        let variance = self.squares as f64 / n - mean * mean;
+        Some((mean, variance.max(0.0).sqrt()))
+    }
+}
+
+impl Integral {
+    fn new(gray: &GrayAlphaImage) -> Self {
+        let (w, h) = gray.dimensions();
+        let width = w as usize + 1;
+        let mut sums = vec![0u64; width * (h as usize + 1)];
+        let mut squares = vec![0u64; sums.len()];
+        let mut counts = vec![0u32; sums.len()];
+        for y in 0..h as usize {
+            let (mut row_sum, mut row_squares, mut row_count) = (0, 0, 0);
+            for x in 0..w as usize {
+                let LumaA([v, a]) = *gray.get_pixel(x as u32, y as u32);
+                if a >= ALPHA_CUTOFF {
+                    row_sum += v as u64;
+                    row_squares += v as u64 * v as u64;
+                    row_count += 1;
+                }
+                let i = (y + 1) * width + x + 1;
+                sums[i] = sums[i - width] + row_sum;
+                squares[i] = squares[i - width] + row_squares;
+                counts[i] = counts[i - width] + row_count;
+            }
+        }
+        Self {
+            width,
+            sums,
+            squares,
+            counts,
+        }
+    }
+
+    /// The sums over the opaque pixels in columns `x0..x1` of rows `y0..y1`.
+    fn window(&self, x0: u32, y0: u32, x1: u32, y1: u32) -> Sums {
+        let at = |x: u32, y: u32| y as usize * self.width + x as usize;
+        let (a, b, c, d) = (at(x0, y0), at(x1, y0), at(x0, y1), at(x1, y1));
+        Sums {
+            count: self.counts[d] + self.counts[a] - self.counts[b] - self.counts[c],
+            sum: self.sums[d] + self.sums[a] - self.sums[b] - self.sums[c],
+            squares: self.squares[d] + self.squares[a] - self.squares[b] - self.squares[c],
+        }
+    }
+
+    /// The window of `size` pixels centered on `(x, y)`, clipped to the
+    /// image.
+    fn around(&self, x: u32, y: u32, size: u32, (w, h): (u32, u32)) -> Sums {
+        let r = size / 2;
+        self.window(
+            x.saturating_sub(r),
+            y.saturating_sub(r),
+            (x + r + 1).min(w),
+            (y + r + 1).min(h),
+        )
+    }
+}
+
+/// A cutoff for every pixel computed by `cutoff` from the mean and standard
+/// deviation of the brightness of the `window`x`window` pixels around it.
+fn local(gray: &GrayAlphaImage, window: u32, cutoff: impl Fn(f64, f64) -> f64) -> GrayImage {
+    let dims = gray.dimensions();
+    let integral = Integral::new(gray);
+    GrayImage::from_fn(dims.0, dims.1, |x, y| {
+        let t = match integral.around(x, y, window, dims).stats() {
+            Some((mean, sd)) => cutoff(mean, sd).round().clamp(0.0, 255.0) as u8,
+            None => 128,
+        };
+        Luma([t])
+    })
+}
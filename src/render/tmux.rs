@@ -0,0 +1,31 @@
+/// Wraps every escape sequence in `text` in tmux's DCS passthrough, which
+/// hands it on to the outer terminal instead of interpreting it. Escape
+/// characters inside are doubled, as tmux expects; the text between the
+/// sequences is left alone.
+pub fn passthrough(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + text.len() / 16 + 16);
+    let mut rest = text;
+    while let Some(start) = rest.find('\x1b') {
+        out.push_str(&rest[..start]);
+        let len = sequence_len(&rest[start..]);
+        out.push_str("\x1bPtmux;");
+        out.push_str(&rest[start..start + len].replace('\x1b', "\x1b\x1b"));
+        out.push_str("\x1b\\");
+        rest = &rest[start + len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Length of the escape sequence `seq` starts with: up to and including the
+/// string terminator (ESC \) or BEL that ends it, or all of `seq` when it is
+/// not terminated.
+fn sequence_len(seq: &str) -> usize {
+    let body = &seq[1..];
+    let st = body.find("\x1b\\").map(|i| i + 3);
+    let bel = body.find('\x07').map(|i| i + 2);
+    match (st, bel) {
+        (Some(st), Some(bel)) => st.min(bel),
+        (end, None) | (None, end) => end.unwrap_or(seq.len()),
+    }
+}
@@ -0,0 +1,63 @@
+use crate::options::RenderOptions;
+use clap::ValueEnum;
+use image::{DynamicImage, Rgba, Rgba32FImage, RgbaImage};
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// How the linear light of an HDR image is squeezed into the range a
+/// terminal can show.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Tonemap {
+    /// x / (1 + x), which keeps every highlight but flattens them
+    #[default]
+    Reinhard,
+    /// The filmic curve of the Academy Color Encoding System, in Narkowicz's
+    /// fit
+    Aces,
+    /// Cut off everything brighter than white
+    Clamp,
+}
+
+impl Tonemap {
+    fn map(self, x: f32) -> f32 {
+        match self {
+            Tonemap::Reinhard => x / (1.0 + x),
+            Tonemap::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+            Tonemap::Clamp => x,
+        }
+    }
+}
+
+/// The sRGB encoding of a linear value between 0 and 1.
+fn srgb(linear: f32) -> u8 {
+    let v = if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Turns HDR images, which the decoders hand over as linear floating point,
+/// into ordinary sRGB ones with the exposure and operator the options ask
+/// for. Leaves every other image alone.
+pub fn apply<'a>(img: &'a DynamicImage, opts: &RenderOptions) -> Cow<'a, DynamicImage> {
+    let hdr: Rgba32FImage = match img {
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => img.to_rgba32f(),
+        _ => return Cow::Borrowed(img),
+    };
+    log::info!(
+        "tone mapping with {:?} at {:+} stops",
+        opts.tonemap,
+        opts.exposure
+    );
+    let gain = opts.exposure.exp2();
+    let rgba = RgbaImage::from_fn(hdr.width(), hdr.height(), |x, y| {
+        let Rgba([r, g, b, a]) = *hdr.get_pixel(x, y);
+        let channel = |v: f32| srgb(opts.tonemap.map((v * gain).max(0.0)));
+        let alpha = (a.clamp(0.0, 1.0) * 255.0).round() as u8;
+        Rgba([channel(r), channel(g), channel(b), alpha])
+    });
+    Cow::Owned(DynamicImage::ImageRgba8(rgba))
+}
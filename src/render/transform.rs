@@ -0,0 +1,182 @@
+use crate::options::RenderOptions;
+use image::{DynamicImage, Rgba, RgbaImage};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::str::FromStr;
+
+/// A position or size given in pixels or as a percentage of the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Pixels(u32),
+    Percent(f32),
+}
+
+impl Length {
+    /// The length in pixels for an image side of `full` pixels.
+    pub(super) fn resolve(self, full: u32) -> u32 {
+        match self {
+            Length::Pixels(px) => px,
+            Length::Percent(p) => (full as f32 * p / 100.0).round() as u32,
+        }
+    }
+}
+
+impl Default for Length {
+    fn default() -> Self {
+        Length::Pixels(0)
+    }
+}
+
+impl FromStr for Length {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.strip_suffix('%') {
+            Some(p) => p
+                .trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|p| (0.0..=100.0).contains(p))
+                .map(Length::Percent)
+                .ok_or_else(|| format!("`{s}` is not a percentage between 0% and 100%")),
+            None => s
+                .parse()
+                .map(Length::Pixels)
+                .map_err(|_| format!("`{s}` is neither a number of pixels nor a percentage")),
+        }
+    }
+}
+
+/// The region --crop keeps, given as `X,Y,W,H`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Crop {
+    pub x: Length,
+    pub y: Length,
+    pub width: Length,
+    pub height: Length,
+}
+
+impl FromStr for Crop {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(',').collect();
+        let [x, y, width, height] = parts[..] else {
+            return Err(format!(
+                "`{s}` is not a region such as `10,10,200,100` or `0,0,50%,50%`"
+            ));
+        };
+        Ok(Crop {
+            x: x.parse()?,
+            y: y.parse()?,
+            width: width.parse()?,
+            height: height.parse()?,
+        })
+    }
+}
+
+impl TryFrom<String> for Crop {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Applies the geometric changes the options ask for, before the image is
+/// fitted to the grid. Leaves the image alone when there are none.
+pub fn apply<'a>(img: &'a DynamicImage, opts: &RenderOptions) -> Cow<'a, DynamicImage> {
+    let mut img = Cow::Borrowed(img);
+    if opts.rotate != 0.0 {
+        img = Cow::Owned(rotate(&img, opts.rotate));
+    }
+    if opts.flip_h {
+        img = Cow::Owned(img.fliph());
+    }
+    if opts.flip_v {
+        img = Cow::Owned(img.flipv());
+    }
+    if let Some(crop) = opts.crop {
+        img = Cow::Owned(self::crop(&img, crop));
+    }
+    img
+}
+
+/// The part of the image inside the region, clipped to the image.
+fn crop(img: &DynamicImage, crop: Crop) -> DynamicImage {
+    let (w, h) = (img.width(), img.height());
+    if w == 0 || h == 0 {
+        return img.clone();
+    }
+    let x = crop.x.resolve(w).min(w.saturating_sub(1));
+    let y = crop.y.resolve(h).min(h.saturating_sub(1));
+    let width = crop.width.resolve(w).clamp(1, w - x);
+    let height = crop.height.resolve(h).clamp(1, h - y);
+    log::info!("cropped {w}x{h} to {width}x{height} at {x},{y}");
+    img.crop_imm(x, y, width, height)
+}
+
+/// Rotates the image clockwise by `degrees`. Right angles are exact, other
+/// angles are sampled bilinearly onto a canvas big enough for the whole
+/// image, with transparent corners.
+fn rotate(img: &DynamicImage, degrees: f32) -> DynamicImage {
+    log::info!("rotated by {degrees} degrees");
+    match degrees {
+        90.0 => return img.rotate90(),
+        180.0 => return img.rotate180(),
+        270.0 => return img.rotate270(),
+        _ => {}
+    }
+    let src = img.to_rgba8();
+    let (w, h) = (src.width() as f32, src.height() as f32);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let out_w = (w * cos.abs() + h * sin.abs()).round().max(1.0);
+    let out_h = (w * sin.abs() + h * cos.abs()).round().max(1.0);
+    let out = RgbaImage::from_fn(out_w as u32, out_h as u32, |x, y| {
+        // Where the center of the output pixel comes from, rotating back
+        // around the centers of both images.
+        let (dx, dy) = (x as f32 + 0.5 - out_w / 2.0, y as f32 + 0.5 - out_h / 2.0);
+        let sx = dx * cos + dy * sin + w / 2.0 - 0.5;
+        let sy = -dx * sin + dy * cos + h / 2.0 - 0.5;
+        bilinear(&src, sx, sy)
+    });
+    DynamicImage::ImageRgba8(out)
+}
+
+/// The color at `(x, y)` blended from the 4 pixels around it, with pixels
+/// outside the image transparent. Colors are weighted by their alpha so
+/// transparent neighbours do not darken the edges.
+fn bilinear(img: &RgbaImage, x: f32, y: f32) -> Rgba<u8> {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let mut sum = [0.0f32; 4];
+    for (ox, oy, weight) in [
+        (0, 0, (1.0 - fx) * (1.0 - fy)),
+        (1, 0, fx * (1.0 - fy)),
+        (0, 1, (1.0 - fx) * fy),
+        (1, 1, fx * fy),
+    ] {
+        let (px, py) = (x0 as i64 + ox, y0 as i64 + oy);
+        if px < 0 || py < 0 || px >= img.width() as i64 || py >= img.height() as i64 {
+            continue;
+        }
+        let [r, g, b, a] = img.get_pixel(px as u32, py as u32).0;
+        let alpha = a as f32 * weight;
+        sum[0] += r as f32 * alpha;
+        sum[1] += g as f32 * alpha;
+        sum[2] += b as f32 * alpha;
+        sum[3] += alpha;
+    }
+    if sum[3] == 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    let channel = |c: f32| (c / sum[3]).round() as u8;
+    Rgba([
+        channel(sum[0]),
+        channel(sum[1]),
+        channel(sum[2]),
+        sum[3].round() as u8,
+    ])
+}
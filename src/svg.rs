@@ -0,0 +1,66 @@
+use crate::error::{Error, Result};
+use image::error::ImageFormatHint;
+use image::{DynamicImage, ImageError};
+use std::path::Path;
+
+/// Whether the start of the data looks like an SVG document, which has no
+/// magic number of its own.
+pub fn is_svg(head: &[u8]) -> bool {
+    let head = &head[..head.len().min(4096)];
+    head.windows(4).any(|w| w == b"<svg")
+}
+
+/// Rasterizes the SVG document at the size the output grid needs, so that
+/// it stays crisp instead of being scaled up from its nominal size.
+#[cfg(feature = "svg")]
+pub fn rasterize(
+    data: &[u8],
+    input: &Path,
+    scale: impl Fn(f32, f32) -> f32,
+) -> Result<DynamicImage> {
+    use image::RgbaImage;
+    use image::error::DecodingError;
+    use resvg::{tiny_skia, usvg};
+
+    /// Largest side of the raster, to keep odd documents from exhausting the
+    /// memory.
+    const MAX_SIDE: f32 = 8192.0;
+
+    let mut options = usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+    let tree = usvg::Tree::from_data(data, &options).map_err(|e| {
+        let hint = ImageFormatHint::Name("SVG".to_string());
+        Error::image(input, ImageError::Decoding(DecodingError::new(hint, e)))
+    })?;
+    let size = tree.size();
+    let scale = scale(size.width(), size.height()).min(MAX_SIDE / size.width().max(size.height()));
+    let width = (size.width() * scale).round().max(1.0) as u32;
+    let height = (size.height() * scale).round().max(1.0) as u32;
+    log::info!(
+        "rasterizing {}x{} SVG at {width}x{height}",
+        size.width(),
+        size.height()
+    );
+    let mut pixmap = tiny_skia::Pixmap::new(width, height).expect("the size is not zero");
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    let rgba = RgbaImage::from_fn(width, height, |x, y| {
+        let pixel = pixmap.pixel(x, y).expect("inside the pixmap").demultiply();
+        [pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()].into()
+    });
+    Ok(DynamicImage::ImageRgba8(rgba))
+}
+
+#[cfg(not(feature = "svg"))]
+pub fn rasterize(_: &[u8], input: &Path, _: impl Fn(f32, f32) -> f32) -> Result<DynamicImage> {
+    use image::error::{UnsupportedError, UnsupportedErrorKind};
+
+    log::warn!("climg was built without the `svg` feature");
+    let hint = ImageFormatHint::Name("SVG".to_string());
+    let e =
+        UnsupportedError::from_format_and_kind(hint.clone(), UnsupportedErrorKind::Format(hint));
+    Err(Error::image(input, ImageError::Unsupported(e)))
+}
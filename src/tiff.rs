@@ -0,0 +1,69 @@
+use crate::error::{Error, Result};
+use image::error::DecodingError;
+use image::{ImageError, ImageFormat};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// The `len` byte unsigned integer at `at`.
+fn read(data: &[u8], at: u64, len: usize, little: bool) -> Option<u64> {
+    let at = usize::try_from(at).ok()?;
+    let bytes = data.get(at..at.checked_add(len)?)?;
+    let value = |v, b: &u8| (v << 8) | u64::from(*b);
+    Some(if little {
+        bytes.iter().rev().fold(0, value)
+    } else {
+        bytes.iter().fold(0, value)
+    })
+}
+
+/// Whether the data starts like a BigTIFF file, the 64-bit variant of TIFF
+/// the image crate does not recognize by itself.
+pub fn is_bigtiff(head: &[u8]) -> bool {
+    head.starts_with(b"II+\0") || head.starts_with(b"MM\0+")
+}
+
+/// Points the header of the TIFF or BigTIFF file at page `page`, counted
+/// from 1, so that the decoder, which only ever reads the first image, reads
+/// that one.
+pub fn seek_page(data: &mut [u8], page: usize, input: &Path) -> Result<()> {
+    let little = data.starts_with(b"II");
+    // BigTIFF widens offsets to 8 bytes and entry counts to 8, and keeps the
+    // first offset after 4 bytes of its own header fields.
+    let (first, offset, count, entry) = match read(data, 2, 2, little) {
+        Some(43) => (8, 8, 8, 20),
+        _ => (4, 4, 2, 12),
+    };
+
+    // Every page is a directory of entries, chained to the next one by the
+    // offset after them.
+    let mut pages = Vec::new();
+    let mut seen = HashSet::new();
+    let mut next = read(data, first, offset, little);
+    while let Some(ifd) = next.filter(|&ifd| ifd != 0 && seen.insert(ifd)) {
+        pages.push(ifd);
+        next = read(data, ifd, count, little)
+            .and_then(|n| n.checked_mul(entry)?.checked_add(ifd + count as u64))
+            .and_then(|at| read(data, at, offset, little));
+    }
+
+    let ifd = page
+        .checked_sub(1)
+        .and_then(|i| pages.get(i))
+        .ok_or_else(|| {
+            let reason = format!("there is no page {page}, the document has {}", pages.len());
+            let e = DecodingError::new(ImageFormat::Tiff.into(), reason);
+            Error::image(input, ImageError::Decoding(e))
+        })?;
+    log::info!("drawing page {page} of {} of the TIFF", pages.len());
+    // The offset is made of the low bytes of the 64-bit one, which come last
+    // when big-endian.
+    let (le, be) = (ifd.to_le_bytes(), ifd.to_be_bytes());
+    let bytes = if little {
+        &le[..offset]
+    } else {
+        &be[8 - offset..]
+    };
+    let at = first as usize;
+    data[at..at + offset].copy_from_slice(bytes);
+    Ok(())
+}
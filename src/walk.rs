@@ -0,0 +1,95 @@
+use crate::camera;
+use crate::error::{Error, Result};
+use crate::glob::natural_cmp;
+use image::ImageFormat;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+/// Which files a recursive walk picks up.
+pub struct WalkOptions<'a> {
+    /// Levels to descend below the root; `None` walks the whole tree.
+    pub max_depth: Option<NonZeroUsize>,
+    /// Accepted extensions; empty accepts every format the `image` crate
+    /// can decode.
+    pub extensions: &'a [String],
+}
+
+/// Collects the images below `root`, sorted naturally within each
+/// directory. Hidden entries are skipped and symlinked directories are not
+/// followed, so a link cycle cannot trap the walk.
+pub fn find_images(root: &Path, opts: &WalkOptions) -> Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    let entries = read_sorted(root).map_err(|e| Error::open(root, e))?;
+    walk(entries, 1, opts, &mut found);
+    if found.is_empty() {
+        log::warn!("no images found in {}", root.display());
+    } else {
+        log::debug!("found {} images in {}", found.len(), root.display());
+    }
+    Ok(found)
+}
+
+fn walk(entries: Vec<fs::DirEntry>, depth: usize, opts: &WalkOptions, found: &mut Vec<PathBuf>) {
+    for entry in entries {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if file_type.is_dir() {
+            if opts.max_depth.is_some_and(|max| depth >= max.get()) {
+                continue;
+            }
+            match read_sorted(&path) {
+                Ok(entries) => walk(entries, depth + 1, opts, found),
+                Err(e) => log::warn!("skipping {}: {e}", path.display()),
+            }
+        } else if path.is_file() && is_image(&path, opts.extensions) {
+            found.push(path);
+        }
+    }
+}
+
+fn read_sorted(dir: &Path) -> std::io::Result<Vec<fs::DirEntry>> {
+    let mut entries: Vec<fs::DirEntry> = fs::read_dir(dir)?
+        .flatten()
+        .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+        .collect();
+    entries.sort_by(|a, b| {
+        natural_cmp(
+            &a.file_name().to_string_lossy(),
+            &b.file_name().to_string_lossy(),
+        )
+    });
+    Ok(entries)
+}
+
+fn is_image(path: &Path, extensions: &[String]) -> bool {
+    let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+        return false;
+    };
+    if extensions.is_empty() {
+        ImageFormat::from_extension(ext).is_some_and(|format| format.reading_enabled())
+            || ["psd", "psb"]
+                .iter()
+                .any(|psd| ext.eq_ignore_ascii_case(psd))
+            || cfg!(feature = "svg") && ext.eq_ignore_ascii_case("svg")
+            || cfg!(feature = "pdf") && ext.eq_ignore_ascii_case("pdf")
+            || cfg!(feature = "jxl") && ext.eq_ignore_ascii_case("jxl")
+            || cfg!(feature = "dicom")
+                && ["dcm", "dicom"].iter().any(|d| ext.eq_ignore_ascii_case(d))
+            || cfg!(feature = "camera-raw")
+                && camera::EXTENSIONS
+                    .iter()
+                    .any(|raw| ext.eq_ignore_ascii_case(raw))
+            || cfg!(feature = "heif")
+                && ["heic", "heif", "avif"]
+                    .iter()
+                    .any(|heif| ext.eq_ignore_ascii_case(heif))
+    } else {
+        extensions
+            .iter()
+            .any(|wanted| wanted.trim_start_matches('.').eq_ignore_ascii_case(ext))
+    }
+}